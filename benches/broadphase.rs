@@ -0,0 +1,75 @@
+// Compares the spatial grid against checking every thingy, for growing
+// thingy counts. Run with `cargo bench --bench broadphase`.
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use reaction_bevy::broadphase::SpatialGrid;
+use reaction_bevy::collision::{overlaps, Collider};
+
+// every ball position is tested against the same set of thingies
+const PROBES: usize = 1000;
+const ROUNDS: u32 = 20;
+
+fn main() {
+    let thingy = Collider::square(30.);
+    let ball = Collider::Circle { radius: 15. };
+    let mut rng = StdRng::seed_from_u64(1);
+
+    println!("{:>8} {:>14} {:>14}", "thingies", "grid", "brute force");
+    for count in [10, 100, 1_000, 5_000, 10_000] {
+        let thingies: Vec<(Entity, Vec2)> = (0..count)
+            .map(|i| {
+                let pos = Vec2::new(rng.gen_range(-380.0..380.0), rng.gen_range(-260.0..260.0));
+                (Entity::from_raw(i), pos)
+            })
+            .collect();
+        let probes: Vec<Vec2> = (0..PROBES)
+            .map(|_| Vec2::new(rng.gen_range(-380.0..380.0), rng.gen_range(-260.0..260.0)))
+            .collect();
+
+        let mut grid = SpatialGrid::default();
+        let mut grid_hits = 0;
+        let grid_time = time(|| {
+            // a tick rebuilds the grid once, then queries it
+            grid.clear();
+            for (ent, pos) in thingies.iter() {
+                grid.insert(*ent, *pos, thingy.half_size());
+            }
+            grid_hits = 0;
+            for probe in probes.iter() {
+                for ent in grid.query(*probe, ball.half_size()) {
+                    let pos = thingies[ent.id() as usize].1;
+                    if overlaps(*probe, &ball, pos, &thingy) {
+                        grid_hits += 1;
+                    }
+                }
+            }
+        });
+
+        let mut brute_hits = 0;
+        let brute_time = time(|| {
+            brute_hits = 0;
+            for probe in probes.iter() {
+                for (_, pos) in thingies.iter() {
+                    if overlaps(*probe, &ball, *pos, &thingy) {
+                        brute_hits += 1;
+                    }
+                }
+            }
+        });
+
+        assert_eq!(grid_hits, brute_hits, "grid missed or doubled hits");
+        println!("{:>8} {:>14?} {:>14?}", count, grid_time, brute_time);
+    }
+}
+
+// average of a few rounds
+fn time(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
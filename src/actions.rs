@@ -0,0 +1,420 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::save::data_dir;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Teleport,
+    Quit,
+    Pause,
+    Confirm,
+    Restart,
+}
+
+impl Action {
+    // replays store actions as a bitmask over this list, so only ever append to it
+    pub const ALL: [Action; 9] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Teleport,
+        Action::Quit,
+        Action::Pause,
+        Action::Confirm,
+        Action::Restart,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "move up",
+            Action::MoveDown => "move down",
+            Action::MoveLeft => "move left",
+            Action::MoveRight => "move right",
+            Action::Teleport => "teleport",
+            Action::Quit => "quit",
+            Action::Pause => "pause",
+            Action::Confirm => "confirm",
+            Action::Restart => "restart",
+        }
+    }
+}
+
+// gamepad buttons are fixed, only the keyboard can be rebound. No two actions
+// share a button, or confirming in the pause menu would also teleport on resume.
+fn pad_buttons(action: Action) -> &'static [GamepadButtonType] {
+    match action {
+        Action::MoveUp => &[GamepadButtonType::DPadUp],
+        Action::MoveDown => &[GamepadButtonType::DPadDown],
+        Action::MoveLeft => &[GamepadButtonType::DPadLeft],
+        Action::MoveRight => &[GamepadButtonType::DPadRight],
+        Action::Teleport => &[GamepadButtonType::West],
+        Action::Quit => &[GamepadButtonType::North],
+        Action::Pause => &[GamepadButtonType::Start],
+        Action::Confirm => &[GamepadButtonType::South],
+        Action::Restart => &[GamepadButtonType::Select],
+    }
+}
+
+// stick deflection below this is ignored
+const DEFAULT_DEADZONE: f32 = 0.2;
+
+fn default_deadzone() -> f32 {
+    DEFAULT_DEADZONE
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ControlMode {
+    // keys, d-pad and stick
+    #[default]
+    Direct,
+    // the ball chases the mouse cursor or a touch point
+    Pointer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyBindings {
+    keys: HashMap<Action, Vec<KeyCode>>,
+    #[serde(default = "default_deadzone")]
+    pub stick_deadzone: f32,
+    #[serde(default)]
+    pub control_mode: ControlMode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = HashMap::from([
+            (Action::MoveUp, vec![KeyCode::Up, KeyCode::W]),
+            (Action::MoveDown, vec![KeyCode::Down, KeyCode::S]),
+            (Action::MoveLeft, vec![KeyCode::Left, KeyCode::A]),
+            (Action::MoveRight, vec![KeyCode::Right, KeyCode::D]),
+            (Action::Teleport, vec![KeyCode::F]),
+            (Action::Quit, vec![KeyCode::Q]),
+            (Action::Pause, vec![KeyCode::Escape, KeyCode::P]),
+            (Action::Confirm, vec![KeyCode::Return, KeyCode::Space]),
+            (Action::Restart, vec![KeyCode::R]),
+        ]);
+        KeyBindings {
+            keys,
+            stick_deadzone: DEFAULT_DEADZONE,
+            control_mode: ControlMode::default(),
+        }
+    }
+}
+
+impl KeyBindings {
+    fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut bindings: KeyBindings = serde_json::from_str(&contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // actions added after the file was written keep their defaults
+        for (action, keys) in KeyBindings::default().keys {
+            bindings.keys.entry(action).or_insert(keys);
+        }
+        Ok(bindings)
+    }
+
+    fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    // The first key bound to `action`, for on-screen hints like "[q] Quit"
+    pub fn key_hint(&self, action: Action) -> String {
+        self.keys(action)
+            .first()
+            .map_or_else(|| "-".to_string(), |k| format!("{:?}", k).to_lowercase())
+    }
+
+    // A key only ever drives one action: if it was bound to another action, that
+    // action gets one of this action's old keys in its place, so the two swap
+    // the one key rather than the other action picking up all of them
+    pub fn rebind(&mut self, action: Action, key: KeyCode) {
+        let previous = self.keys.insert(action, vec![key]).unwrap_or_default();
+        for (other, keys) in self.keys.iter_mut() {
+            if *other == action {
+                continue;
+            }
+            let slot = match keys.iter().position(|k| *k == key) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            // the old key in the same slot, so a primary key swaps for a primary key
+            let swapped = previous
+                .get(slot)
+                .or_else(|| previous.first())
+                .filter(|k| **k != key && !keys.contains(k));
+            match swapped {
+                Some(k) => keys[slot] = *k,
+                None => {
+                    keys.remove(slot);
+                }
+            }
+        }
+    }
+}
+
+// Where the bindings are stored, None when they shouldn't be persisted (headless)
+pub struct BindingsPath(Option<PathBuf>);
+
+impl BindingsPath {
+    pub fn save(&self, bindings: &KeyBindings) {
+        if let Some(path) = &self.0 {
+            if let Err(e) = bindings.write(path) {
+                eprintln!("could not write key bindings {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+// What the player is asking for this frame, independent of which keys are bound.
+// Gameplay reads this instead of the keyboard.
+#[derive(Debug, Default)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    // analog stick direction, deadzone applied, length at most 1
+    stick: Vec2,
+    // set while a replay is feeding the actions
+    pub replaying: bool,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn set(&mut self, pressed: HashSet<Action>) {
+        self.just_pressed = pressed.difference(&self.pressed).copied().collect();
+        self.pressed = pressed;
+    }
+
+    pub fn stick(&self) -> Vec2 {
+        self.stick
+    }
+
+    // The stick is quantized so a replay, which stores it as i8s, reproduces it exactly
+    pub fn set_stick(&mut self, stick: Vec2) {
+        let (x, y) = quantize(stick);
+        self.stick = Vec2::new(x as f32, y as f32) / 127.;
+    }
+
+    pub fn snapshot(&self) -> ActionSnapshot {
+        let mask = Action::ALL
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| self.pressed(**a))
+            .fold(0, |mask, (i, _)| mask | 1 << i);
+        ActionSnapshot {
+            mask,
+            stick: quantize(self.stick),
+        }
+    }
+
+    pub fn apply(&mut self, snapshot: ActionSnapshot) {
+        let pressed = Action::ALL
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| snapshot.mask & (1 << i) != 0)
+            .map(|(_, a)| *a)
+            .collect();
+        self.set(pressed);
+        let (x, y) = snapshot.stick;
+        self.stick = Vec2::new(x as f32, y as f32) / 127.;
+    }
+}
+
+fn quantize(v: Vec2) -> (i8, i8) {
+    let q = (v.clamp(Vec2::splat(-1.), Vec2::ONE) * 127.).round();
+    (q.x as i8, q.y as i8)
+}
+
+// One tick of input: a bit per `Action::ALL` entry plus the stick
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ActionSnapshot {
+    pub mask: u32,
+    pub stick: (i8, i8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub struct ReadActions;
+
+pub struct ActionPlugin {
+    pub persist: bool,
+}
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        let path = if self.persist {
+            data_dir().map(|d| d.join("bindings.json"))
+        } else {
+            None
+        };
+        let bindings = match &path {
+            Some(p) if p.exists() => KeyBindings::load(p).unwrap_or_else(|e| {
+                eprintln!("could not load key bindings {}: {}", p.display(), e);
+                KeyBindings::default()
+            }),
+            _ => KeyBindings::default(),
+        };
+
+        app.insert_resource(bindings)
+            .insert_resource(BindingsPath(path))
+            .insert_resource(ActionState::default())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                read_actions.label(ReadActions).after(InputSystem),
+            );
+    }
+}
+
+// Gamepads are looked up every frame, so a controller plugged in mid-run
+// works straight away
+fn read_actions(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    bindings: Res<KeyBindings>,
+    mut actions: ResMut<ActionState>,
+) {
+    if actions.replaying {
+        return;
+    }
+    let pressed = Action::ALL
+        .iter()
+        .filter(|a| {
+            keyboard_input.any_pressed(bindings.keys(**a).iter().copied())
+                || gamepads.iter().any(|gamepad| {
+                    pad_buttons(**a)
+                        .iter()
+                        .any(|b| buttons.pressed(GamepadButton::new(*gamepad, *b)))
+                })
+        })
+        .copied()
+        .collect();
+    actions.set(pressed);
+
+    let mut stick = Vec2::ZERO;
+    for gamepad in gamepads.iter() {
+        let x = axes
+            .get(GamepadAxis::new(*gamepad, GamepadAxisType::LeftStickX))
+            .unwrap_or(0.);
+        let y = axes
+            .get(GamepadAxis::new(*gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.);
+        stick += Vec2::new(x, y);
+    }
+    actions.set_stick(apply_deadzone(stick, bindings.stick_deadzone));
+}
+
+// radial deadzone, rescaled so movement starts from zero at the edge of the deadzone
+fn apply_deadzone(stick: Vec2, deadzone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= deadzone {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - deadzone) / (1. - deadzone)).min(1.);
+    stick / length * scaled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // no key drives two actions
+    fn assert_unique(bindings: &KeyBindings) {
+        let mut seen = HashSet::new();
+        for action in Action::ALL {
+            for key in bindings.keys(action) {
+                assert!(seen.insert(*key), "{:?} is bound twice", key);
+            }
+        }
+    }
+
+    #[test]
+    fn pad_buttons_are_not_shared() {
+        let mut seen = HashSet::new();
+        for action in Action::ALL {
+            for button in pad_buttons(action) {
+                assert!(seen.insert(*button), "{:?} is used twice", button);
+            }
+        }
+    }
+
+    #[test]
+    fn rebinds_to_a_free_key() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::Teleport, KeyCode::T);
+        assert_eq!(bindings.keys(Action::Teleport), [KeyCode::T]);
+        assert_eq!(bindings.keys(Action::Quit), [KeyCode::Q]);
+        assert_unique(&bindings);
+    }
+
+    #[test]
+    fn swaps_only_the_replaced_key() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::MoveUp, KeyCode::S);
+        assert_eq!(bindings.keys(Action::MoveUp), [KeyCode::S]);
+        assert_eq!(bindings.keys(Action::MoveDown), [KeyCode::Down, KeyCode::W]);
+        assert_unique(&bindings);
+    }
+
+    #[test]
+    fn swaps_a_single_key() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::Teleport, KeyCode::Q);
+        assert_eq!(bindings.keys(Action::Teleport), [KeyCode::Q]);
+        assert_eq!(bindings.keys(Action::Quit), [KeyCode::F]);
+        assert_unique(&bindings);
+    }
+
+    #[test]
+    fn falls_back_to_the_first_old_key() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::Quit, KeyCode::P);
+        assert_eq!(bindings.keys(Action::Quit), [KeyCode::P]);
+        assert_eq!(bindings.keys(Action::Pause), [KeyCode::Escape, KeyCode::Q]);
+        assert_unique(&bindings);
+    }
+
+    #[test]
+    fn rebinding_to_an_own_key_keeps_just_that_key() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::MoveUp, KeyCode::W);
+        assert_eq!(bindings.keys(Action::MoveUp), [KeyCode::W]);
+        assert_eq!(bindings.keys(Action::MoveDown), [KeyCode::Down, KeyCode::S]);
+        assert_unique(&bindings);
+    }
+
+    #[test]
+    fn drops_the_key_when_there_is_nothing_to_swap() {
+        let mut bindings = KeyBindings::default();
+        bindings.keys.insert(Action::Teleport, Vec::new());
+        bindings.rebind(Action::Teleport, KeyCode::Q);
+        assert_eq!(bindings.keys(Action::Teleport), [KeyCode::Q]);
+        assert!(bindings.keys(Action::Quit).is_empty());
+    }
+}
@@ -1,27 +1,93 @@
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
+use reaction_bevy::broadphase::{rebuild_grid, SpatialGrid};
+use reaction_bevy::collision::{overlaps, Collider};
 
-use crate::{AppState, CollisionEvent};
+use crate::actions::{Action, ActionState};
+use crate::playfield::PlayField;
+use crate::thingy::nearest_thingy;
+use crate::{on_fixed_update, AppState, CollisionEvent, FixedUpdateStage, TIMESTEP};
 
-use crate::components::{Ball, Thingy};
+use crate::components::{Ball, BallPreset, BallSettings, Thingy, Velocity};
+
+const BALL_RADIUS: f32 = 15.0;
 
 pub struct BallPlugin;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum BallSystem {
+    Movement,
+    Integrate,
+    Walls,
+    Broadphase,
+    Collide,
+}
+
 impl Plugin for BallPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(spawn_ball).add_system_set(
-            SystemSet::on_update(AppState::InGame)
-                .with_system(ball_movement)
-                .with_system(ball_collide),
-        );
-        // .add_system(ball_movement)
-        // .add_system(ball_collide);
+        // the default preset is used until the settings asset has loaded
+        app.insert_resource(BallPreset::default())
+            .insert_resource(SpatialGrid::default())
+            .add_startup_system(load_ball_settings)
+            .add_system(apply_ball_settings)
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_ball))
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                on_fixed_update(AppState::InGame)
+                    .with_system(ball_movement.label(BallSystem::Movement))
+                    .with_system(
+                        ball_integrate
+                            .label(BallSystem::Integrate)
+                            .after(BallSystem::Movement),
+                    )
+                    .with_system(
+                        ball_walls
+                            .label(BallSystem::Walls)
+                            .after(BallSystem::Integrate),
+                    )
+                    .with_system(
+                        rebuild_grid
+                            .label(BallSystem::Broadphase)
+                            .after(BallSystem::Walls),
+                    )
+                    .with_system(
+                        ball_collide
+                            .label(BallSystem::Collide)
+                            .after(BallSystem::Broadphase),
+                    ),
+            );
+    }
+}
+
+fn load_ball_settings(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handle: Handle<BallSettings> = asset_server.load("default.ball.json");
+    commands.insert_resource(handle);
+}
+
+fn apply_ball_settings(
+    mut ev_asset: EventReader<AssetEvent<BallSettings>>,
+    assets: Res<Assets<BallSettings>>,
+    handle: Res<Handle<BallSettings>>,
+    mut preset: ResMut<BallPreset>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Created { handle: h } | AssetEvent::Modified { handle: h } = ev {
+            if *h != *handle {
+                continue;
+            }
+            if let Some(settings) = assets.get(h) {
+                match settings.presets.get(&settings.preset) {
+                    Some(p) => *preset = p.clone(),
+                    None => eprintln!("unknown ball preset: {}", settings.preset),
+                }
+            }
+        }
     }
 }
 
 fn spawn_ball(mut commands: Commands) {
     let ball = shapes::Circle {
-        radius: 15.0,
+        radius: BALL_RADIUS,
         center: Vec2 { x: 0.0, y: 0.0 },
     };
     commands
@@ -30,18 +96,29 @@ fn spawn_ball(mut commands: Commands) {
             DrawMode::Fill(FillMode::color(Color::YELLOW)),
             Transform::default(),
         ))
-        .insert(Ball);
+        .insert(Ball)
+        .insert(Collider::Circle {
+            radius: BALL_RADIUS,
+        })
+        .insert(Velocity::default());
 }
 
 fn ball_collide(
     mut commands: Commands,
-    ball_positions: Query<&Transform, With<Ball>>,
-    thingy_positions: Query<(Entity, &Transform), With<Thingy>>,
+    grid: Res<SpatialGrid>,
+    balls: Query<(&Transform, &Collider), With<Ball>>,
+    thingies: Query<(&Transform, &Collider), With<Thingy>>,
     mut ev_collision: EventWriter<CollisionEvent>,
 ) {
-    for ball in ball_positions.iter() {
-        for (ent, t) in thingy_positions.iter() {
-            if collision(ball.translation, t.translation) {
+    for (ball, ball_collider) in balls.iter() {
+        let ball_pos = ball.translation.truncate();
+        for ent in grid.query(ball_pos, ball_collider.half_size()) {
+            // the grid holds every collider, the ball itself included
+            let (t, collider) = match thingies.get(ent) {
+                Ok(thingy) => thingy,
+                Err(_) => continue,
+            };
+            if overlaps(ball_pos, ball_collider, t.translation.truncate(), collider) {
                 commands.entity(ent).despawn();
                 ev_collision.send(CollisionEvent(ent));
             }
@@ -49,41 +126,83 @@ fn ball_collide(
     }
 }
 
-fn collision(a: Vec3, b: Vec3) -> bool {
-    let radius = 15.0;
-    let thingy_side = 30.0;
-    if a.x - radius < (b.x + thingy_side)
-        && a.x + radius > b.x
-        && a.y - radius < (b.y + thingy_side)
-        && a.y + radius > b.y
-    {
-        return true;
-    }
-    false
-}
-
+// Steer the ball's velocity towards the direction the player is holding
 fn ball_movement(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut ball_positions: Query<&mut Transform, With<Ball>>,
+    actions: Res<ActionState>,
+    preset: Res<BallPreset>,
+    mut balls: Query<(&mut Transform, &mut Velocity), With<Ball>>,
     thingy: Query<&Transform, (With<Thingy>, Without<Ball>)>,
+    mut teleport_held: Local<bool>,
 ) {
-    for mut ball in ball_positions.iter_mut() {
-        if keyboard_input.pressed(KeyCode::Left) || keyboard_input.pressed(KeyCode::A) {
-            ball.translation.x -= 2.;
-        }
-        if keyboard_input.pressed(KeyCode::Right) || keyboard_input.pressed(KeyCode::D) {
-            ball.translation.x += 2.;
+    let dt = TIMESTEP as f32;
+
+    // edge-detect per tick rather than using just_pressed, which is per frame
+    // and would fire on every tick of a frame that runs several
+    let teleport = actions.pressed(Action::Teleport) && !*teleport_held;
+    *teleport_held = actions.pressed(Action::Teleport);
+
+    let mut direction = Vec2::ZERO;
+    if actions.pressed(Action::MoveLeft) {
+        direction.x -= 1.;
+    }
+    if actions.pressed(Action::MoveRight) {
+        direction.x += 1.;
+    }
+    if actions.pressed(Action::MoveDown) {
+        direction.y -= 1.;
+    }
+    if actions.pressed(Action::MoveUp) {
+        direction.y += 1.;
+    }
+    // diagonals aren't faster than straight lines
+    direction = direction.normalize_or_zero();
+    // the analog stick only counts when no direction is held digitally
+    if direction == Vec2::ZERO {
+        direction = actions.stick();
+    }
+
+    for (mut ball, mut velocity) in balls.iter_mut() {
+        if direction != Vec2::ZERO {
+            let target = direction * preset.max_speed;
+            let change = (target - velocity.0).clamp_length_max(preset.acceleration * dt);
+            velocity.0 += change;
+        } else {
+            let speed = (velocity.0.length() - preset.friction * dt).max(0.);
+            velocity.0 = velocity.0.normalize_or_zero() * speed;
         }
-        if keyboard_input.pressed(KeyCode::Down) || keyboard_input.pressed(KeyCode::S) {
-            ball.translation.y -= 2.;
+
+        if teleport {
+            if let Some(t) = nearest_thingy(ball.translation.truncate(), thingy.iter()) {
+                ball.translation.x = t.translation.x;
+                ball.translation.y = t.translation.y;
+                velocity.0 = Vec2::ZERO;
+            }
         }
-        if keyboard_input.pressed(KeyCode::Up) || keyboard_input.pressed(KeyCode::W) {
-            ball.translation.y += 2.;
+    }
+}
+
+fn ball_integrate(mut balls: Query<(&mut Transform, &Velocity), With<Ball>>) {
+    for (mut ball, velocity) in balls.iter_mut() {
+        ball.translation += (velocity.0 * TIMESTEP as f32).extend(0.);
+    }
+}
+
+// Keep the ball inside the play field, bouncing it off the walls
+fn ball_walls(
+    field: Res<PlayField>,
+    mut balls: Query<(&mut Transform, &mut Velocity, &Collider), With<Ball>>,
+) {
+    for (mut ball, mut velocity, collider) in balls.iter_mut() {
+        let min = field.min + collider.half_size();
+        let max = field.max - collider.half_size();
+
+        if ball.translation.x < min.x || ball.translation.x > max.x {
+            ball.translation.x = ball.translation.x.clamp(min.x, max.x);
+            velocity.0.x *= -field.restitution;
         }
-        if keyboard_input.just_pressed(KeyCode::F) {
-            let t = thingy.single();
-            ball.translation.x = t.translation.x;
-            ball.translation.y = t.translation.y;
+        if ball.translation.y < min.y || ball.translation.y > max.y {
+            ball.translation.y = ball.translation.y.clamp(min.y, max.y);
+            velocity.0.y *= -field.restitution;
         }
     }
 }
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::collision::Collider;
+
+// roughly twice a thingy, so most entities sit in one to four cells
+const DEFAULT_CELL_SIZE: f32 = 64.0;
+
+// Uniform grid over every entity with a collider, rebuilt each tick.
+// Hit tests only look at entities sharing a cell instead of all of them.
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        SpatialGrid::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    // empties the cells but keeps their allocations for the next rebuild
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+    }
+
+    // The entity goes in every cell its box touches
+    pub fn insert(&mut self, entity: Entity, pos: Vec2, half_size: Vec2) {
+        let (min, max) = self.cell_range(pos, half_size);
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                self.cells.entry((x, y)).or_default().push(entity);
+            }
+        }
+    }
+
+    // Entities whose cells the box touches, each once. They are only
+    // candidates, the caller still does the exact test.
+    pub fn query(&self, pos: Vec2, half_size: Vec2) -> Vec<Entity> {
+        let (min, max) = self.cell_range(pos, half_size);
+        let mut found = Vec::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    found.extend_from_slice(cell);
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    fn cell_range(&self, pos: Vec2, half_size: Vec2) -> ((i32, i32), (i32, i32)) {
+        (self.cell(pos - half_size), self.cell(pos + half_size))
+    }
+
+    fn cell(&self, p: Vec2) -> (i32, i32) {
+        (
+            (p.x / self.cell_size).floor() as i32,
+            (p.y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+pub fn rebuild_grid(
+    mut grid: ResMut<SpatialGrid>,
+    colliders: Query<(Entity, &Transform, &Collider)>,
+) {
+    grid.clear();
+    for (ent, transform, collider) in colliders.iter() {
+        grid.insert(ent, transform.translation.truncate(), collider.half_size());
+    }
+}
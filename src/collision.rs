@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+
+// Shape used for hit tests, centered on the entity's transform
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub enum Collider {
+    Circle { radius: f32 },
+    // axis aligned box, rotation is ignored
+    Aabb { half_extents: Vec2 },
+}
+
+impl Collider {
+    pub fn square(side: f32) -> Self {
+        Collider::Aabb {
+            half_extents: Vec2::splat(side / 2.),
+        }
+    }
+
+    // half the size of the box around the shape
+    pub fn half_size(&self) -> Vec2 {
+        match *self {
+            Collider::Circle { radius } => Vec2::splat(radius),
+            Collider::Aabb { half_extents } => half_extents,
+        }
+    }
+}
+
+// Shapes that only touch don't count as overlapping
+pub fn overlaps(a_pos: Vec2, a: &Collider, b_pos: Vec2, b: &Collider) -> bool {
+    match (*a, *b) {
+        (Collider::Circle { radius: ra }, Collider::Circle { radius: rb }) => {
+            circle_circle(a_pos, ra, b_pos, rb)
+        }
+        (Collider::Circle { radius }, Collider::Aabb { half_extents }) => {
+            circle_aabb(a_pos, radius, b_pos, half_extents)
+        }
+        (Collider::Aabb { half_extents }, Collider::Circle { radius }) => {
+            circle_aabb(b_pos, radius, a_pos, half_extents)
+        }
+        (Collider::Aabb { half_extents: ha }, Collider::Aabb { half_extents: hb }) => {
+            let d = (a_pos - b_pos).abs();
+            d.x < ha.x + hb.x && d.y < ha.y + hb.y
+        }
+    }
+}
+
+pub fn circle_circle(a: Vec2, a_radius: f32, b: Vec2, b_radius: f32) -> bool {
+    let reach = a_radius + b_radius;
+    a.distance_squared(b) < reach * reach
+}
+
+// The circle hits the box when the box's closest point to the center is
+// inside the circle, which gets the rounded corners right
+pub fn circle_aabb(center: Vec2, radius: f32, box_center: Vec2, half_extents: Vec2) -> bool {
+    let closest = center.clamp(box_center - half_extents, box_center + half_extents);
+    closest.distance_squared(center) < radius * radius
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BALL: Collider = Collider::Circle { radius: 10. };
+
+    fn thingy() -> Collider {
+        Collider::square(30.)
+    }
+
+    #[test]
+    fn circle_touching_box_edge_is_not_a_hit() {
+        // box edge at x = 15, circle reaches from 15 to 35
+        assert!(!overlaps(Vec2::new(25., 0.), &BALL, Vec2::ZERO, &thingy()));
+        assert!(overlaps(Vec2::new(24.9, 0.), &BALL, Vec2::ZERO, &thingy()));
+    }
+
+    #[test]
+    fn circle_off_box_corner_is_not_a_hit() {
+        // the bounding boxes overlap, but the corner at (15, 15) is 11.3 away
+        let center = Vec2::new(23., 23.);
+        assert!((center - Vec2::splat(15.)).max_element() < 10.);
+        assert!(!overlaps(center, &BALL, Vec2::ZERO, &thingy()));
+        assert!(overlaps(Vec2::new(21., 21.), &BALL, Vec2::ZERO, &thingy()));
+    }
+
+    #[test]
+    fn circles_touching_are_not_a_hit() {
+        let other = Collider::Circle { radius: 5. };
+        assert!(!overlaps(Vec2::ZERO, &BALL, Vec2::new(0., 15.), &other));
+        assert!(overlaps(Vec2::ZERO, &BALL, Vec2::new(0., 14.9), &other));
+    }
+
+    #[test]
+    fn boxes_touching_are_not_a_hit() {
+        let small = Collider::square(10.);
+        assert!(!overlaps(Vec2::ZERO, &thingy(), Vec2::new(20., 3.), &small));
+        assert!(overlaps(Vec2::ZERO, &thingy(), Vec2::new(19.9, 3.), &small));
+    }
+
+    #[test]
+    fn argument_order_does_not_matter() {
+        let cases = [
+            (Vec2::new(24., 0.), BALL, Vec2::ZERO, thingy()),
+            (Vec2::new(23., 23.), BALL, Vec2::ZERO, thingy()),
+            (
+                Vec2::ZERO,
+                BALL,
+                Vec2::new(8., 8.),
+                Collider::Circle { radius: 5. },
+            ),
+            (
+                Vec2::ZERO,
+                thingy(),
+                Vec2::new(25., 0.),
+                Collider::square(10.),
+            ),
+        ];
+        for (a_pos, a, b_pos, b) in cases {
+            assert_eq!(
+                overlaps(a_pos, &a, b_pos, &b),
+                overlaps(b_pos, &b, a_pos, &a),
+                "{:?} at {} against {:?} at {}",
+                a,
+                a_pos,
+                b,
+                b_pos
+            );
+        }
+    }
+}
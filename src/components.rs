@@ -1,11 +1,41 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
+use reaction_story::Story;
+
+use crate::placement::PlacementRules;
 
 #[derive(Component)]
 pub struct Ball;
 
+#[derive(Component, Default)]
+pub struct Velocity(pub Vec2);
+
 #[derive(Component)]
 pub struct Thingy;
 
+// How a thingy behaves, set from its entry in `ThingyKinds`
+#[derive(Component, Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ThingyKind {
+    Static,
+    // moves in a straight line, bouncing off the edges of the spawn area
+    Drift { speed: f32 },
+    // runs from the ball while it's within range
+    Flee { speed: f32, range: f32 },
+    // jumps somewhere else every so many seconds
+    Teleport { every: f32 },
+    // costs score when touched, and disappears after its lifetime
+    Decoy { penalty: i32, lifetime: f32 },
+}
+
+// The fixed tick a thingy was spawned on, and when it disappears by itself
+#[derive(Component, Debug)]
+pub struct Spawned {
+    pub tick: u32,
+    pub expires: Option<u32>,
+}
+
 #[derive(Component)]
 pub struct ScoreText;
 
@@ -20,17 +50,207 @@ pub struct TrophyText {
     pub score: i32,
 }
 
+// A storyline file, see `Story` for the format
 #[derive(Debug, serde::Deserialize, bevy::reflect::TypeUuid)]
+#[serde(transparent)]
 #[uuid = "413be529-bfeb-41b3-9db0-4b8b38022c46"]
-pub struct StoryLines {
-    pub storylines: Vec<StoryLine>,
+pub struct StoryLines(pub Story);
+
+#[derive(Component)]
+pub struct TextLine;
+
+#[derive(Component)]
+pub struct GameOverText;
+
+// the quit hint in the corner, redrawn when the quit key is rebound
+#[derive(Component)]
+pub struct QuitText;
+
+#[derive(Debug, serde::Deserialize, bevy::reflect::TypeUuid)]
+#[uuid = "6d1f1c8e-3b0a-4f43-a0a4-5c2b9e7d8f11"]
+pub struct TrophyDefs {
+    pub trophies: Vec<TrophyDef>,
 }
 
 #[derive(Debug, serde::Deserialize)]
-pub struct StoryLine {
-    pub line: String,
-    pub appears_at: i32,
+pub struct TrophyDef {
+    pub name: String,
+    pub description: String,
+    pub score: i32,
+    // hex strings, e.g. "ff0000"
+    pub square_color: String,
+    pub circle_color: String,
 }
 
-#[derive(Component)]
-pub struct TextLine;
+#[derive(Debug, serde::Deserialize, bevy::reflect::TypeUuid)]
+#[uuid = "0b6a9d4e-7c1f-4a52-9e38-2f5d8c6b1a70"]
+pub struct BallSettings {
+    // name of the entry in `presets` to use
+    pub preset: String,
+    pub presets: HashMap<String, BallPreset>,
+}
+
+// How the ball handles, speeds in units per second
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct BallPreset {
+    pub max_speed: f32,
+    // how fast the ball reaches max_speed while a direction is held
+    pub acceleration: f32,
+    // how fast the ball comes to a stop once nothing is held
+    pub friction: f32,
+}
+
+impl Default for BallPreset {
+    // effectively no inertia, like the ball has always moved
+    fn default() -> Self {
+        BallPreset {
+            max_speed: 120.,
+            acceleration: 100_000.,
+            friction: 100_000.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, bevy::reflect::TypeUuid)]
+#[uuid = "c4e2a7b1-5d3f-4e8a-b6c9-1f0d2e3a4b5c"]
+pub struct SpawnSettings {
+    // the last stage whose conditions are met is used
+    pub stages: Vec<SpawnStage>,
+    #[serde(default)]
+    pub placement: PlacementRules,
+}
+
+// How many thingies are kept alive, times in seconds
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SpawnStage {
+    #[serde(default)]
+    pub from_score: i32,
+    #[serde(default)]
+    pub after_seconds: f32,
+    pub alive: usize,
+    // wait between spawns, 0 refills straight away
+    #[serde(default)]
+    pub interval: f32,
+    // thingies not collected in time disappear, None keeps them forever
+    #[serde(default)]
+    pub lifetime: Option<f32>,
+}
+
+impl Default for SpawnSettings {
+    // one thingy at a time, replaced as soon as it's collected
+    fn default() -> Self {
+        SpawnSettings {
+            stages: vec![SpawnStage {
+                from_score: 0,
+                after_seconds: 0.,
+                alive: 1,
+                interval: 0.,
+                lifetime: None,
+            }],
+            placement: PlacementRules::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, bevy::reflect::TypeUuid)]
+#[uuid = "8f3b6c2d-9a1e-4b7f-a5d0-e6c4b2a19f38"]
+pub struct ThingyKinds {
+    pub kinds: Vec<ThingyKindDef>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ThingyKindDef {
+    #[serde(flatten)]
+    pub kind: ThingyKind,
+    // chance of being picked relative to the other weights
+    pub weight: u32,
+    // hex string, e.g. "3a0000", black when missing
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+impl Default for ThingyKinds {
+    // only the classic black square
+    fn default() -> Self {
+        ThingyKinds {
+            kinds: vec![ThingyKindDef {
+                kind: ThingyKind::Static,
+                weight: 1,
+                color: None,
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, bevy::reflect::TypeUuid)]
+#[uuid = "2a7d5e91-4c3b-4f6e-8d2a-b9c1e0f7a364"]
+pub struct FadeSettings {
+    pub thingy_alpha: FadeCurve,
+    #[serde(default)]
+    pub background: Option<ColorFade>,
+    #[serde(default)]
+    pub ball: Option<ColorFade>,
+    #[serde(default)]
+    pub storyline: Option<ColorFade>,
+}
+
+// Keyframes over the score, each easing towards the next. Before the first
+// and after the last key the value is held, and it's always kept in 0..=1.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FadeCurve {
+    pub keys: Vec<FadeKey>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FadeKey {
+    pub score: f32,
+    pub value: f32,
+    // how the value moves from this key to the next
+    #[serde(default)]
+    pub ease: Easing,
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    // holds the value until the next key
+    Step,
+}
+
+// Blends between two hex colors by a curve, 0 is `from` and 1 is `to`
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ColorFade {
+    pub from: String,
+    pub to: String,
+    pub curve: FadeCurve,
+}
+
+impl Default for FadeSettings {
+    // thingies fade out over the last hundred kills, nothing else changes
+    fn default() -> Self {
+        FadeSettings {
+            thingy_alpha: FadeCurve {
+                keys: vec![
+                    FadeKey {
+                        score: 100.,
+                        value: 1.,
+                        ease: Easing::Linear,
+                    },
+                    FadeKey {
+                        score: 200.,
+                        value: 0.,
+                        ease: Easing::Linear,
+                    },
+                ],
+            },
+            background: None,
+            ball: None,
+            storyline: None,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+// read when present and no other config file is given
+const DEFAULT_CONFIG: &str = "launch.json";
+
+// smallest window that still leaves room to spawn thingies inside the play field
+const MIN_WINDOW_WIDTH: f32 = 320.;
+const MIN_WINDOW_HEIGHT: f32 = 240.;
+
+#[derive(Debug, Default)]
+pub struct LaunchArgs {
+    pub headless: bool,
+    pub seed: Option<u64>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    // these override the config file
+    pub story: Option<String>,
+    pub start_score: Option<i32>,
+    pub game_over_score: Option<i32>,
+    pub window: Option<(f32, f32)>,
+}
+
+impl LaunchArgs {
+    pub fn from_env() -> Self {
+        let mut args = LaunchArgs::default();
+        let mut iter = env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--headless" => args.headless = true,
+                "--seed" => match iter.next().map(|s| s.parse()) {
+                    Some(Ok(seed)) => args.seed = Some(seed),
+                    _ => eprintln!("--seed expects a number"),
+                },
+                "--record" => match iter.next() {
+                    Some(path) => args.record = Some(path.into()),
+                    None => eprintln!("--record expects a file path"),
+                },
+                "--replay" => match iter.next() {
+                    Some(path) => args.replay = Some(path.into()),
+                    None => eprintln!("--replay expects a file path"),
+                },
+                "--config" => match iter.next() {
+                    Some(path) => args.config = Some(path.into()),
+                    None => eprintln!("--config expects a file path"),
+                },
+                "--story" => match iter.next() {
+                    Some(path) => args.story = Some(path),
+                    None => eprintln!("--story expects an asset path"),
+                },
+                "--start-score" => match iter.next().map(|s| s.parse()) {
+                    Some(Ok(score)) => args.start_score = Some(score),
+                    _ => eprintln!("--start-score expects a number"),
+                },
+                "--game-over-score" => match iter.next().map(|s| s.parse()) {
+                    Some(Ok(score)) => args.game_over_score = Some(score),
+                    _ => eprintln!("--game-over-score expects a number"),
+                },
+                "--window" => match iter.next().as_deref().and_then(parse_size) {
+                    Some(size) => args.window = Some(size),
+                    None => eprintln!("--window expects a size like 800x600"),
+                },
+                other => eprintln!("unknown argument: {}", other),
+            }
+        }
+        args
+    }
+}
+
+fn parse_size(s: &str) -> Option<(f32, f32)> {
+    let (w, h) = s.split_once('x')?;
+    Some((w.parse().ok()?, h.parse().ok()?))
+}
+
+// What the game launches with: the config file, if any, with the command
+// line on top
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LaunchConfig {
+    // storyline file inside the assets folder
+    pub story: String,
+    pub start_score: i32,
+    pub game_over_score: i32,
+    pub window_width: f32,
+    pub window_height: f32,
+}
+
+impl Default for LaunchConfig {
+    fn default() -> Self {
+        LaunchConfig {
+            story: "storylines.json".to_string(),
+            start_score: 0,
+            game_over_score: 201,
+            window_width: 800.,
+            window_height: 600.,
+        }
+    }
+}
+
+impl LaunchConfig {
+    pub fn resolve(args: &LaunchArgs) -> Self {
+        let path = args.config.clone().or_else(|| {
+            let default = PathBuf::from(DEFAULT_CONFIG);
+            default.exists().then_some(default)
+        });
+        let mut config = match &path {
+            Some(p) => LaunchConfig::load(p).unwrap_or_else(|e| {
+                eprintln!("could not load config file {}: {}", p.display(), e);
+                LaunchConfig::default()
+            }),
+            None => LaunchConfig::default(),
+        };
+
+        if let Some(story) = &args.story {
+            config.story = story.clone();
+        }
+        if let Some(score) = args.start_score {
+            config.start_score = score;
+        }
+        if let Some(score) = args.game_over_score {
+            config.game_over_score = score;
+        }
+        if let Some((width, height)) = args.window {
+            config.window_width = width;
+            config.window_height = height;
+        }
+
+        // also catches NaN from the config file
+        if !(config.window_width >= MIN_WINDOW_WIDTH && config.window_height >= MIN_WINDOW_HEIGHT) {
+            eprintln!(
+                "window {}x{} is too small, using at least {}x{}",
+                config.window_width, config.window_height, MIN_WINDOW_WIDTH, MIN_WINDOW_HEIGHT
+            );
+            config.window_width = config.window_width.max(MIN_WINDOW_WIDTH);
+            config.window_height = config.window_height.max(MIN_WINDOW_HEIGHT);
+        }
+        if config.start_score >= config.game_over_score {
+            eprintln!(
+                "start score {} is already past the game over score {}",
+                config.start_score, config.game_over_score
+            );
+        }
+        config
+    }
+
+    fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
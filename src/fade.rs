@@ -0,0 +1,372 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::components::{
+    Ball, ColorFade, Easing, FadeCurve, FadeSettings, TextLine, Thingy, UIElement,
+};
+use crate::playfield::PlayField;
+use crate::{AppState, GameOverScore, Score, ThingyAlpha};
+
+// toggles the curve graph, for tuning
+const GRAPH_KEY: KeyCode = KeyCode::F3;
+const GRAPH_SIZE: Vec2 = Vec2::new(300., 100.);
+
+pub struct FadePlugin;
+
+impl Plugin for FadePlugin {
+    fn build(&self, app: &mut App) {
+        // the default curve is used until the asset has loaded
+        app.insert_resource(FadeSettings::default())
+            .add_startup_system(load_fade_settings)
+            .add_system(apply_fade_settings)
+            .add_system(draw_fade_graph)
+            .add_system(move_graph_marker)
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(apply_fade));
+    }
+}
+
+#[derive(Component)]
+struct FadeGraph;
+
+#[derive(Component)]
+struct GraphMarker;
+
+impl Easing {
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2. - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    1. - (2. - 2. * t).powi(2) / 2.
+                }
+            }
+            Easing::Step => 0.,
+        }
+    }
+}
+
+impl FadeCurve {
+    // keys are sorted by score when the settings load
+    pub fn sample(&self, score: f32) -> f32 {
+        let keys = &self.keys;
+        let value = match keys.iter().position(|k| k.score > score) {
+            None => keys.last().map_or(0., |k| k.value),
+            Some(0) => keys[0].value,
+            Some(i) => {
+                let (a, b) = (&keys[i - 1], &keys[i]);
+                let t = (score - a.score) / (b.score - a.score);
+                a.value + (b.value - a.value) * a.ease.apply(t)
+            }
+        };
+        value.clamp(0., 1.)
+    }
+}
+
+fn parse_color(hex: &str) -> Option<Color> {
+    Color::hex(hex.trim_start_matches('#')).ok()
+}
+
+impl ColorFade {
+    fn color(&self, score: f32) -> Option<Color> {
+        let (from, to) = (parse_color(&self.from)?, parse_color(&self.to)?);
+        let t = self.curve.sample(score);
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Some(Color::rgba(
+            lerp(from.r(), to.r()),
+            lerp(from.g(), to.g()),
+            lerp(from.b(), to.b()),
+            lerp(from.a(), to.a()),
+        ))
+    }
+}
+
+fn load_fade_settings(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handle: Handle<FadeSettings> = asset_server.load("default.fade.json");
+    commands.insert_resource(handle);
+}
+
+fn apply_fade_settings(
+    mut ev_asset: EventReader<AssetEvent<FadeSettings>>,
+    assets: Res<Assets<FadeSettings>>,
+    handle: Res<Handle<FadeSettings>>,
+    mut settings: ResMut<FadeSettings>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Created { handle: h } | AssetEvent::Modified { handle: h } = ev {
+            if *h != *handle {
+                continue;
+            }
+            if let Some(s) = assets.get(h) {
+                *settings = s.clone();
+                validate(&mut settings);
+            }
+        }
+    }
+}
+
+// Sorts the keys and reports anything that won't fade as written
+fn validate(settings: &mut FadeSettings) {
+    let mut curves = vec![("thingy_alpha", &mut settings.thingy_alpha)];
+    for (name, fade) in [
+        ("background", &mut settings.background),
+        ("ball", &mut settings.ball),
+        ("storyline", &mut settings.storyline),
+    ] {
+        if let Some(fade) = fade {
+            for hex in [&fade.from, &fade.to] {
+                if parse_color(hex).is_none() {
+                    eprintln!("invalid {} fade color: {}", name, hex);
+                }
+            }
+            curves.push((name, &mut fade.curve));
+        }
+    }
+    for (name, curve) in curves {
+        if curve.keys.is_empty() {
+            eprintln!("{} fade curve has no keys", name);
+        }
+        curve.keys.sort_by(|a, b| a.score.total_cmp(&b.score));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_fade(
+    settings: Res<FadeSettings>,
+    score: Res<Score>,
+    mut alpha: ResMut<ThingyAlpha>,
+    mut clear_color: ResMut<ClearColor>,
+    mut thingies: Query<&mut DrawMode, With<Thingy>>,
+    mut balls: Query<&mut DrawMode, (With<Ball>, Without<Thingy>)>,
+    mut textline: Query<&mut Text, With<TextLine>>,
+) {
+    let score = score.0 as f32;
+
+    alpha.0 = settings.thingy_alpha.sample(score);
+    // only the alpha changes, thingy kinds keep their own color
+    for mut draw_mode in thingies.iter_mut() {
+        if let DrawMode::Fill(fill) = &mut *draw_mode {
+            fill.color.set_a(alpha.0);
+        }
+    }
+
+    if let Some(color) = settings.background.as_ref().and_then(|f| f.color(score)) {
+        clear_color.0 = color;
+    }
+    if let Some(color) = settings.ball.as_ref().and_then(|f| f.color(score)) {
+        for mut draw_mode in balls.iter_mut() {
+            *draw_mode = DrawMode::Fill(FillMode::color(color));
+        }
+    }
+    if let Some(color) = settings.storyline.as_ref().and_then(|f| f.color(score)) {
+        for mut text in textline.iter_mut() {
+            // the alpha belongs to the line's own fade in and out
+            for section in text.sections.iter_mut() {
+                let alpha = section.style.color.a();
+                section.style.color = color;
+                section.style.color.set_a(alpha);
+            }
+        }
+    }
+}
+
+fn graph_origin(field: &PlayField) -> Vec2 {
+    Vec2::new(field.max.x - GRAPH_SIZE.x - 10., field.min.y + 10.)
+}
+
+// Every curve plotted over 0..=game over score, with a marker at the current score
+fn draw_fade_graph(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<FadeSettings>,
+    field: Res<PlayField>,
+    gameoverscore: Res<GameOverScore>,
+    graph: Query<Entity, With<FadeGraph>>,
+    mut shown: Local<bool>,
+) {
+    let toggled = keyboard_input.just_pressed(GRAPH_KEY);
+    if toggled {
+        *shown = !*shown;
+    }
+    let redraw = toggled || (*shown && settings.is_changed());
+    if !redraw {
+        return;
+    }
+    for ent in graph.iter() {
+        commands.entity(ent).despawn();
+    }
+    if !*shown {
+        return;
+    }
+
+    let origin = graph_origin(&field);
+    let backdrop = shapes::Rectangle {
+        origin: RectangleOrigin::BottomLeft,
+        extents: GRAPH_SIZE,
+    };
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &backdrop,
+            DrawMode::Fill(FillMode::color(Color::rgba(0., 0., 0., 0.6))),
+            Transform::from_translation(origin.extend(50.)),
+        ))
+        .insert(FadeGraph)
+        .insert(UIElement);
+
+    let max_score = gameoverscore.0 as f32;
+    let mut curves = vec![(Color::WHITE, &settings.thingy_alpha)];
+    for fade in [&settings.background, &settings.ball, &settings.storyline]
+        .into_iter()
+        .flatten()
+    {
+        let color = parse_color(&fade.to).unwrap_or(Color::GRAY);
+        curves.push((color, &fade.curve));
+    }
+    for (color, curve) in curves {
+        let mut path = PathBuilder::new();
+        let steps = GRAPH_SIZE.x as i32;
+        for step in 0..=steps {
+            let x = step as f32 / steps as f32;
+            let point = Vec2::new(x, curve.sample(x * max_score)) * GRAPH_SIZE;
+            if step == 0 {
+                path.move_to(point);
+            } else {
+                path.line_to(point);
+            }
+        }
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &path.build(),
+                DrawMode::Stroke(StrokeMode::new(color, 1.5)),
+                Transform::from_translation(origin.extend(51.)),
+            ))
+            .insert(FadeGraph)
+            .insert(UIElement);
+    }
+
+    let marker = shapes::Line(Vec2::ZERO, Vec2::new(0., GRAPH_SIZE.y));
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &marker,
+            DrawMode::Stroke(StrokeMode::new(Color::RED, 1.)),
+            Transform::from_translation(origin.extend(52.)),
+        ))
+        .insert(FadeGraph)
+        .insert(GraphMarker)
+        .insert(UIElement);
+}
+
+fn move_graph_marker(
+    score: Res<Score>,
+    gameoverscore: Res<GameOverScore>,
+    field: Res<PlayField>,
+    mut markers: Query<&mut Transform, With<GraphMarker>>,
+) {
+    let x = (score.0 as f32 / gameoverscore.0 as f32).clamp(0., 1.) * GRAPH_SIZE.x;
+    for mut transform in markers.iter_mut() {
+        transform.translation.x = graph_origin(&field).x + x;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::FadeKey;
+
+    fn key(score: f32, value: f32, ease: Easing) -> FadeKey {
+        FadeKey { score, value, ease }
+    }
+
+    fn curve(keys: Vec<FadeKey>) -> FadeCurve {
+        FadeCurve { keys }
+    }
+
+    #[test]
+    fn holds_the_value_outside_the_keys() {
+        let c = curve(vec![
+            key(100., 0.8, Easing::Linear),
+            key(200., 0.2, Easing::Linear),
+        ]);
+        assert_eq!(c.sample(0.), 0.8);
+        assert_eq!(c.sample(100.), 0.8);
+        assert_eq!(c.sample(200.), 0.2);
+        assert_eq!(c.sample(500.), 0.2);
+    }
+
+    #[test]
+    fn interpolates_between_keys() {
+        let c = curve(vec![
+            key(0., 0., Easing::Linear),
+            key(100., 1., Easing::Linear),
+        ]);
+        assert!((c.sample(25.) - 0.25).abs() < 1e-6);
+        let c = curve(vec![
+            key(0., 0., Easing::EaseIn),
+            key(100., 1., Easing::Linear),
+        ]);
+        assert!((c.sample(50.) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clamps_values_to_0_1() {
+        let c = curve(vec![
+            key(0., -1., Easing::Linear),
+            key(100., 2., Easing::Linear),
+        ]);
+        assert_eq!(c.sample(0.), 0.);
+        assert_eq!(c.sample(100.), 1.);
+        // halfway is 0.5 and stays as it is
+        assert!((c.sample(50.) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn step_holds_until_the_next_key() {
+        let c = curve(vec![
+            key(0., 1., Easing::Step),
+            key(100., 0., Easing::Linear),
+        ]);
+        assert_eq!(c.sample(0.), 1.);
+        assert_eq!(c.sample(99.), 1.);
+        assert_eq!(c.sample(100.), 0.);
+    }
+
+    #[test]
+    fn easings_start_at_0_and_end_at_1() {
+        for ease in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(ease.apply(0.), 0., "{:?}", ease);
+            assert!((ease.apply(1.) - 1.).abs() < 1e-6, "{:?}", ease);
+        }
+        assert_eq!(Easing::Step.apply(0.99), 0.);
+    }
+
+    #[test]
+    fn validate_sorts_keys_given_out_of_order() {
+        let mut settings = FadeSettings {
+            thingy_alpha: curve(vec![
+                key(200., 0., Easing::Linear),
+                key(100., 1., Easing::Linear),
+            ]),
+            ..default()
+        };
+        validate(&mut settings);
+        let c = &settings.thingy_alpha;
+        assert_eq!(c.sample(0.), 1.);
+        assert!((c.sample(150.) - 0.5).abs() < 1e-6);
+        assert_eq!(c.sample(300.), 0.);
+    }
+
+    #[test]
+    fn empty_curve_is_zero() {
+        let c = curve(Vec::new());
+        assert_eq!(c.sample(0.), 0.);
+        assert_eq!(c.sample(150.), 0.);
+    }
+}
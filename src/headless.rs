@@ -0,0 +1,82 @@
+use bevy::{app::AppExit, input::InputSystem, prelude::*};
+
+use crate::actions::ReadActions;
+use crate::components::{Ball, Thingy, ThingyKind};
+use crate::thingy::nearest_thingy;
+use crate::{AppState, Score};
+
+// give up if the autopilot hasn't reached the game over by now
+const MAX_FRAMES: u32 = 500_000;
+
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FrameCount(0))
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                autopilot.after(InputSystem).before(ReadActions),
+            )
+            .add_system(frame_limit)
+            .add_system_set(SystemSet::on_enter(AppState::GameOver).with_system(exit_on_gameover));
+    }
+}
+
+struct FrameCount(u32);
+
+// thingies the autopilot can steer for, with their kind so decoys can be left alone
+type Targets<'w, 's> =
+    Query<'w, 's, (&'static Transform, &'static ThingyKind), (With<Thingy>, Without<Ball>)>;
+
+// Scripted input: steer the ball towards the nearest thingy by pressing the default movement keys
+fn autopilot(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    ball: Query<&Transform, With<Ball>>,
+    thingy: Targets,
+) {
+    for key in [KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down] {
+        keyboard_input.release(key);
+    }
+
+    let ball = match ball.get_single() {
+        Ok(b) => b,
+        Err(_) => return,
+    };
+    // decoys cost score, leave them be
+    let targets = thingy
+        .iter()
+        .filter(|(_, kind)| !matches!(kind, ThingyKind::Decoy { .. }))
+        .map(|(t, _)| t);
+    let thingy = match nearest_thingy(ball.translation.truncate(), targets) {
+        Some(t) => t,
+        None => return,
+    };
+
+    let diff = thingy.translation - ball.translation;
+    if diff.x < -2. {
+        keyboard_input.press(KeyCode::Left);
+    } else if diff.x > 2. {
+        keyboard_input.press(KeyCode::Right);
+    }
+    if diff.y < -2. {
+        keyboard_input.press(KeyCode::Down);
+    } else if diff.y > 2. {
+        keyboard_input.press(KeyCode::Up);
+    }
+}
+
+fn frame_limit(mut frames: ResMut<FrameCount>, score: Res<Score>) {
+    frames.0 += 1;
+    if frames.0 > MAX_FRAMES {
+        eprintln!("headless run did not reach game over, score {}", score.0);
+        std::process::exit(1);
+    }
+}
+
+fn exit_on_gameover(frames: Res<FrameCount>, score: Res<Score>, mut exit: EventWriter<AppExit>) {
+    println!(
+        "headless run finished: score {} in {} frames",
+        score.0, frames.0
+    );
+    exit.send(AppExit);
+}
@@ -0,0 +1,3 @@
+// Game code that the benches build on as well
+pub mod broadphase;
+pub mod collision;
@@ -1,65 +1,219 @@
-use bevy::{app::AppExit, prelude::*};
+use bevy::{
+    app::AppExit, asset::AssetServerSettings, ecs::schedule::ShouldRun, prelude::*,
+    time::FixedTimestep,
+};
 use bevy_common_assets::json::JsonAssetPlugin;
 use bevy_prototype_lyon::prelude::*;
 
+mod actions;
 mod ball;
 mod components;
+mod config;
+mod fade;
+mod headless;
+mod menu;
+mod pause;
+mod placement;
+mod playfield;
+mod pointer;
+mod replay;
+mod rng;
+mod save;
 mod storyline;
 mod thingy;
 mod trophy;
 
+use actions::{Action, ActionPlugin, ActionState, KeyBindings};
 use ball::BallPlugin;
-use components::{Ball, ScoreText, StoryLines, TextLine, Thingy, UIElement};
+use components::{
+    Ball, BallSettings, FadeSettings, GameOverText, QuitText, ScoreText, SpawnSettings, StoryLines,
+    TextLine, Thingy, ThingyKinds, TrophyDefs, UIElement,
+};
+use config::{LaunchArgs, LaunchConfig};
+use fade::FadePlugin;
+use headless::HeadlessPlugin;
+use menu::MenuPlugin;
+use pause::{PauseInput, PausePlugin};
+use playfield::PlayField;
+use pointer::PointerPlugin;
+use replay::{Replay, ReplayPlugin};
+use rng::GameRng;
+use save::SavePlugin;
 use storyline::StoryLinePlugin;
-use thingy::ThingyPlugin;
+use thingy::{ScoreKills, ThingyPlugin};
 use trophy::TrophyPlugin;
 
 const DARK_GRAY: Color = Color::rgb(0.31, 0.31, 0.31);
 
-const WINDOW_WIDTH: f32 = 800.0;
-const WINDOW_HEIGHT: f32 = 600.0;
+// length of one gameplay tick in seconds
+const TIMESTEP: f64 = 1. / 60.;
+
+// Gameplay systems run here, once per TIMESTEP, ahead of the regular update
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+struct FixedUpdateStage;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+struct RestartInput;
+
+// SystemSet::on_update asks to be checked again until the state driver has run in
+// the same stage, and the driver is in Update, so in FixedUpdateStage it would spin
+// forever. Fixed tick systems use this instead. A transition queued by a tick is only
+// applied in Update, so the ticks left in that frame are skipped.
+fn on_fixed_update(state: AppState) -> SystemSet {
+    SystemSet::new().with_run_criteria(
+        move |current: Res<State<AppState>>, time: Res<Time>, mut queued: Local<bool>| {
+            // time changes once a frame, the state when Update applies a transition or
+            // when a tick queues one
+            if current.is_added() || (current.is_changed() && !time.is_changed()) {
+                *queued = true;
+            } else if time.is_changed() {
+                *queued = false;
+            }
+            if !*queued && current.current() == &state {
+                ShouldRun::Yes
+            } else {
+                ShouldRun::No
+            }
+        },
+    )
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum AppState {
+    MainMenu,
     InGame,
+    Paused,
     GameOver,
 }
 
 fn main() {
-    let window = WindowDescriptor {
-        title: "re-action bevy".to_string(),
-        width: WINDOW_WIDTH,
-        height: WINDOW_HEIGHT,
-        ..default()
+    let args = LaunchArgs::from_env();
+    let config = LaunchConfig::resolve(&args);
+    // runs that jump into the story aren't real plays, so they leave the save alone
+    let persist_save = !args.headless && config.start_score == 0;
+
+    let replay = args
+        .replay
+        .as_ref()
+        .map(|path| Replay::load(path).expect("could not load replay file"));
+
+    // a replay only reproduces the session with the seed it was recorded with
+    let rng = GameRng::new(replay.as_ref().map(|r| r.seed).or(args.seed));
+    println!("seed: {}", rng.seed);
+
+    // automated and replayed runs skip the menu and start straight into play
+    let initial_state = if args.headless || replay.is_some() || args.record.is_some() {
+        AppState::InGame
+    } else {
+        AppState::MainMenu
     };
 
-    App::new()
+    let mut app = App::new();
+
+    // headless runs step once per frame so they aren't bound to the wall clock
+    let fixed_update = if args.headless {
+        SystemStage::parallel()
+    } else {
+        SystemStage::parallel().with_run_criteria(FixedTimestep::step(TIMESTEP))
+    };
+    app.add_stage_before(CoreStage::Update, FixedUpdateStage, fixed_update);
+
+    if args.headless {
+        // no window or renderer, the game is driven by the autopilot in HeadlessPlugin
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(bevy::transform::TransformPlugin)
+            .add_plugin(bevy::hierarchy::HierarchyPlugin)
+            .add_plugin(bevy::input::InputPlugin)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_plugin(HeadlessPlugin);
+    } else {
+        let window = WindowDescriptor {
+            title: "re-action bevy".to_string(),
+            width: config.window_width,
+            height: config.window_height,
+            ..default()
+        };
+
+        app.insert_resource(window)
+            // edited asset files are picked up while the game runs, but not
+            // while recording or replaying, which have to stay reproducible
+            .insert_resource(AssetServerSettings {
+                watch_for_changes: replay.is_none() && args.record.is_none(),
+                ..default()
+            })
+            .add_plugins(DefaultPlugins)
+            .add_plugin(ShapePlugin);
+    }
+
+    if let Some(replay) = replay {
+        app.add_plugin(ReplayPlugin::Playback(replay));
+    } else if let Some(path) = args.record {
+        app.add_plugin(ReplayPlugin::Record {
+            path,
+            seed: rng.seed,
+        });
+    }
+
+    app
         // resources
         .insert_resource(ClearColor(DARK_GRAY))
-        .insert_resource(window)
-        .insert_resource(Score(0))
-        .insert_resource(GameOverScore(201))
+        .insert_resource(Score(config.start_score))
+        .insert_resource(StartScore(config.start_score))
+        .insert_resource(GameOverScore(config.game_over_score))
         .insert_resource(ThingyAlpha(1.0))
+        .insert_resource(PlayField::from_window(
+            config.window_width,
+            config.window_height,
+        ))
+        .insert_resource(rng)
         // events
         .add_event::<CollisionEvent>()
         // plugins
-        .add_plugins(DefaultPlugins)
         .add_plugin(JsonAssetPlugin::<StoryLines>::new(&["json"]))
-        .add_plugin(ShapePlugin)
+        .add_plugin(JsonAssetPlugin::<TrophyDefs>::new(&["trophies.json"]))
+        .add_plugin(JsonAssetPlugin::<BallSettings>::new(&["ball.json"]))
+        .add_plugin(JsonAssetPlugin::<SpawnSettings>::new(&["spawns.json"]))
+        .add_plugin(JsonAssetPlugin::<ThingyKinds>::new(&["thingies.json"]))
+        .add_plugin(JsonAssetPlugin::<FadeSettings>::new(&["fade.json"]))
+        .add_plugin(ActionPlugin {
+            persist: !args.headless,
+        })
+        .add_plugin(PointerPlugin)
         .add_plugin(BallPlugin)
         .add_plugin(ThingyPlugin)
+        .add_plugin(FadePlugin)
         .add_plugin(TrophyPlugin)
-        .add_plugin(StoryLinePlugin)
+        .add_plugin(SavePlugin {
+            persist: persist_save,
+        })
+        .add_plugin(StoryLinePlugin {
+            path: config.story.clone(),
+        })
+        .add_plugin(MenuPlugin)
+        .add_plugin(PausePlugin)
         // state
-        .add_state(AppState::InGame)
+        .add_state(initial_state)
         // start up systems (run only once)
         .add_startup_system(setup_camera)
         .add_startup_system(setup_ui_texts)
         .add_startup_system(draw_static_ui)
         // systems (these run on every frame)
         .add_system(exit_system)
+        .add_system(quit_hint_system)
+        .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(reset_run))
         .add_system_set(SystemSet::on_update(AppState::InGame).with_system(scoretext_update_system))
+        .add_system_set_to_stage(
+            FixedUpdateStage,
+            on_fixed_update(AppState::InGame)
+                .with_system(gameover_check.after(ScoreKills).after(PauseInput)),
+        )
+        .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(despawn_gameplay))
         .add_system_set(SystemSet::on_enter(AppState::GameOver).with_system(gameover_system))
+        .add_system_set_to_stage(
+            FixedUpdateStage,
+            on_fixed_update(AppState::GameOver).with_system(restart_system.label(RestartInput)),
+        )
+        .add_system_set(SystemSet::on_exit(AppState::GameOver).with_system(clear_gameover))
         // run
         .run();
 }
@@ -67,6 +221,10 @@ fn main() {
 #[derive(Debug)]
 struct Score(i32);
 
+// where every run's score starts
+#[derive(Debug)]
+struct StartScore(i32);
+
 #[derive(Debug)]
 struct ThingyAlpha(f32);
 
@@ -78,160 +236,257 @@ struct CollisionEvent(Entity);
 fn gameover_system(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    ball: Query<Entity, With<Ball>>,
-    thingy: Query<Entity, With<Thingy>>,
-    textline: Query<Entity, With<TextLine>>,
     mut scoretext: Query<&mut Text, With<ScoreText>>,
+    rng: Res<GameRng>,
+    bindings: Res<KeyBindings>,
 ) {
     println!("GAME OVER");
 
-    let ball = ball.single();
-    commands.entity(ball).despawn();
-
-    let thingy = thingy.single();
-    commands.entity(thingy).despawn();
-
-    let textline = textline.single();
-    commands.entity(textline).despawn();
-
     for mut text in &mut scoretext {
         text.sections[0].value = "Kills:".to_string();
     }
 
-    commands.spawn_bundle(
-        TextBundle::from_section(
-            "EVEN THE SMALLEST action",
-            TextStyle {
-                font: asset_server.load("ProggyClean.ttf"),
-                font_size: 30.0,
-                color: Color::WHITE,
-            },
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "EVEN THE SMALLEST action",
+                TextStyle {
+                    font: asset_server.load("ProggyClean.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(100.0),
+                    left: Val::Px(50.0),
+                    ..default()
+                },
+                ..default()
+            }),
         )
-        .with_style(Style {
-            align_self: AlignSelf::FlexEnd,
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(100.0),
-                left: Val::Px(50.0),
+        .insert(GameOverText);
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "HAS A reaction.",
+                TextStyle {
+                    font: asset_server.load("ProggyClean.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(150.0),
+                    left: Val::Px(270.0),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        }),
-    );
-    commands.spawn_bundle(
-        TextBundle::from_section(
-            "HAS A reaction.",
-            TextStyle {
-                font: asset_server.load("ProggyClean.ttf"),
-                font_size: 30.0,
-                color: Color::WHITE,
-            },
+            }),
         )
-        .with_style(Style {
-            align_self: AlignSelf::FlexEnd,
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(150.0),
-                left: Val::Px(270.0),
+        .insert(GameOverText);
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "SOMETIMES IT'S GOOD TO JUST",
+                TextStyle {
+                    font: asset_server.load("ProggyClean.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(250.0),
+                    left: Val::Px(50.0),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        }),
-    );
-    commands.spawn_bundle(
-        TextBundle::from_section(
-            "SOMETIMES IT'S GOOD TO JUST",
-            TextStyle {
-                font: asset_server.load("ProggyClean.ttf"),
-                font_size: 30.0,
-                color: Color::WHITE,
-            },
+            }),
         )
-        .with_style(Style {
-            align_self: AlignSelf::FlexEnd,
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(250.0),
-                left: Val::Px(50.0),
+        .insert(GameOverText);
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "STOP AND THINK WHAT EFFECT",
+                TextStyle {
+                    font: asset_server.load("ProggyClean.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(300.0),
+                    left: Val::Px(80.0),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        }),
-    );
-    commands.spawn_bundle(
-        TextBundle::from_section(
-            "STOP AND THINK WHAT EFFECT",
-            TextStyle {
-                font: asset_server.load("ProggyClean.ttf"),
-                font_size: 30.0,
-                color: Color::WHITE,
-            },
+            }),
+        )
+        .insert(GameOverText);
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "YOUR CURRENT ACTIONS HAVE.",
+                TextStyle {
+                    font: asset_server.load("ProggyClean.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(350.0),
+                    left: Val::Px(100.0),
+                    ..default()
+                },
+                ..default()
+            }),
         )
-        .with_style(Style {
-            align_self: AlignSelf::FlexEnd,
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(300.0),
-                left: Val::Px(80.0),
+        .insert(GameOverText);
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "THANK YOU FOR PLAYING.",
+                TextStyle {
+                    font: asset_server.load("ProggyClean.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(500.0),
+                    left: Val::Px(50.0),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        }),
-    );
-    commands.spawn_bundle(
-        TextBundle::from_section(
-            "YOUR CURRENT ACTIONS HAVE.",
-            TextStyle {
-                font: asset_server.load("ProggyClean.ttf"),
-                font_size: 30.0,
-                color: Color::WHITE,
-            },
+            }),
         )
-        .with_style(Style {
-            align_self: AlignSelf::FlexEnd,
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(350.0),
-                left: Val::Px(100.0),
+        .insert(GameOverText);
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                format!("seed: {}", rng.seed),
+                TextStyle {
+                    font: asset_server.load("ProggyClean.ttf"),
+                    font_size: 15.0,
+                    color: Color::GRAY,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(5.0),
+                    right: Val::Px(5.0),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        }),
-    );
-    commands.spawn_bundle(
-        TextBundle::from_section(
-            "THANK YOU FOR PLAYING.",
-            TextStyle {
-                font: asset_server.load("ProggyClean.ttf"),
-                font_size: 30.0,
-                color: Color::WHITE,
-            },
+            }),
         )
-        .with_style(Style {
-            align_self: AlignSelf::FlexEnd,
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(500.0),
-                left: Val::Px(50.0),
+        .insert(GameOverText);
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                format!("[{}] Restart", bindings.key_hint(Action::Restart)),
+                TextStyle {
+                    font: asset_server.load("ProggyClean.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(150.0),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        }),
-    );
+            }),
+        )
+        .insert(GameOverText);
 }
 
-fn scoretext_update_system(
-    mut query: Query<&mut Text, With<ScoreText>>,
-    score: Res<Score>,
-    gameoverscore: Res<GameOverScore>,
+// Edge-detected per tick like pause, so a replay restarts on the same tick
+fn restart_system(
+    actions: Res<ActionState>,
     mut app_state: ResMut<State<AppState>>,
+    mut restart_held: Local<bool>,
+) {
+    let restart = actions.pressed(Action::Restart) && !*restart_held;
+    *restart_held = actions.pressed(Action::Restart);
+
+    if restart {
+        app_state.set(AppState::InGame).unwrap();
+    }
+}
+
+fn clear_gameover(
+    mut commands: Commands,
+    gameover_texts: Query<Entity, With<GameOverText>>,
+    mut scoretext: Query<&mut Text, With<ScoreText>>,
+) {
+    for ent in gameover_texts.iter() {
+        commands.entity(ent).despawn();
+    }
+    for mut text in &mut scoretext {
+        text.sections[0].value = "Score: ".to_string();
+    }
+}
+
+// Every run starts from a clean slate, the gameplay entities are
+// spawned by their plugins when InGame is entered
+fn reset_run(
+    mut score: ResMut<Score>,
+    start_score: Res<StartScore>,
+    mut alpha: ResMut<ThingyAlpha>,
 ) {
+    score.0 = start_score.0;
+    alpha.0 = 1.0;
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_gameplay(
+    mut commands: Commands,
+    gameplay: Query<Entity, Or<(With<Ball>, With<Thingy>, With<TextLine>)>>,
+) {
+    for ent in gameplay.iter() {
+        commands.entity(ent).despawn();
+    }
+}
+
+fn scoretext_update_system(mut query: Query<&mut Text, With<ScoreText>>, score: Res<Score>) {
     for mut text in &mut query {
         text.sections[1].value = score.0.to_string();
     }
+}
 
-    if score.0 == gameoverscore.0 {
-        app_state.set(AppState::GameOver).unwrap();
+// Checked every tick, so the run ends on the same tick whatever the frame rate
+fn gameover_check(
+    score: Res<Score>,
+    gameoverscore: Res<GameOverScore>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    // a pause queued by the same tick goes first, the check runs again on resume
+    if score.0 >= gameoverscore.0 {
+        let _ = app_state.set(AppState::GameOver);
     }
 }
 
@@ -269,12 +524,12 @@ fn setup_ui_texts(mut commands: Commands, asset_server: Res<AssetServer>) {
         .insert(UIElement);
 }
 
-fn draw_static_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
-    // [q] quit
+fn draw_static_ui(mut commands: Commands, asset_server: Res<AssetServer>, field: Res<PlayField>) {
+    // [q] quit, the key is filled in by quit_hint_system
     commands
         .spawn_bundle(
             TextBundle::from_section(
-                "[q] Quit",
+                "",
                 TextStyle {
                     font: asset_server.load("ProggyClean.ttf"),
                     font_size: 30.0,
@@ -292,13 +547,14 @@ fn draw_static_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..default()
             }),
         )
+        .insert(QuitText)
         .insert(UIElement);
 
     // Lines
     let line_width = 2.0;
     let upper_line = shapes::Line(
-        Vec2::new(-(WINDOW_WIDTH / 2.0) + 20., (WINDOW_HEIGHT / 2.) - 40.),
-        Vec2::new((WINDOW_WIDTH / 2.0) - 20., (WINDOW_HEIGHT / 2.) - 40.),
+        Vec2::new(field.min.x, field.max.y),
+        Vec2::new(field.max.x, field.max.y),
     );
 
     commands
@@ -310,8 +566,8 @@ fn draw_static_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
         .insert(UIElement);
 
     let lower_line = shapes::Line(
-        Vec2::new(-(WINDOW_WIDTH / 2.0) + 20., -(WINDOW_HEIGHT / 2.0) + 40.),
-        Vec2::new((WINDOW_WIDTH / 2.0) - 20., -(WINDOW_HEIGHT / 2.) + 40.),
+        Vec2::new(field.min.x, field.min.y),
+        Vec2::new(field.max.x, field.min.y),
     );
 
     commands
@@ -323,12 +579,30 @@ fn draw_static_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
         .insert(UIElement);
 }
 
+fn quit_hint_system(bindings: Res<KeyBindings>, mut hint: Query<&mut Text, With<QuitText>>) {
+    // also true on the first frame, once the text has been spawned
+    if !bindings.is_changed() {
+        return;
+    }
+    for mut text in &mut hint {
+        text.sections[0].value = format!("[{}] Quit", bindings.key_hint(Action::Quit));
+    }
+}
+
 fn setup_camera(mut commands: Commands) {
     commands.spawn_bundle(Camera2dBundle::default());
 }
 
-fn exit_system(keyboard_input: Res<Input<KeyCode>>, mut exit: EventWriter<AppExit>) {
-    if keyboard_input.pressed(KeyCode::Q) {
+fn exit_system(
+    actions: Res<ActionState>,
+    app_state: Res<State<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // the menu has its own quit entry, and the quit key may be getting rebound there
+    if *app_state.current() == AppState::MainMenu {
+        return;
+    }
+    if actions.pressed(Action::Quit) {
         exit.send(AppExit);
     }
 }
@@ -0,0 +1,391 @@
+use bevy::{app::AppExit, prelude::*, window::WindowMode};
+
+use crate::actions::{Action, ActionState, BindingsPath, ControlMode, KeyBindings};
+use crate::components::{Trophy, TrophyDefs, UIElement};
+use crate::save::SaveData;
+use crate::AppState;
+
+pub struct MenuPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuPage {
+    Main,
+    Options,
+    Trophies,
+}
+
+struct Menu {
+    page: MenuPage,
+    selected: usize,
+    // waiting for a key press to bind to this action
+    rebinding: Option<Action>,
+}
+
+#[derive(Component)]
+struct MenuScreen;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+struct MenuInput;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuEntry {
+    Start,
+    Options,
+    Trophies,
+    Quit,
+    Fullscreen,
+    Control,
+    Deadzone,
+    Bind(Action),
+    Back,
+}
+
+impl MenuPage {
+    fn entries(&self) -> &'static [MenuEntry] {
+        match self {
+            MenuPage::Main => &[
+                MenuEntry::Start,
+                MenuEntry::Options,
+                MenuEntry::Trophies,
+                MenuEntry::Quit,
+            ],
+            MenuPage::Options => &[
+                MenuEntry::Fullscreen,
+                MenuEntry::Control,
+                MenuEntry::Deadzone,
+                MenuEntry::Bind(Action::MoveUp),
+                MenuEntry::Bind(Action::MoveDown),
+                MenuEntry::Bind(Action::MoveLeft),
+                MenuEntry::Bind(Action::MoveRight),
+                MenuEntry::Bind(Action::Teleport),
+                MenuEntry::Bind(Action::Pause),
+                MenuEntry::Bind(Action::Quit),
+                MenuEntry::Bind(Action::Confirm),
+                MenuEntry::Bind(Action::Restart),
+                MenuEntry::Back,
+            ],
+            MenuPage::Trophies => &[MenuEntry::Back],
+        }
+    }
+}
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Menu {
+            page: MenuPage::Main,
+            selected: 0,
+            rebinding: None,
+        })
+        .add_system_set(
+            SystemSet::on_update(AppState::MainMenu)
+                .with_system(menu_input.label(MenuInput))
+                .with_system(draw_menu.after(MenuInput))
+                .with_system(hide_hud),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::MainMenu)
+                .with_system(close_menu)
+                .with_system(show_hud),
+        );
+    }
+}
+
+pub enum MenuAction {
+    Up,
+    Down,
+    Confirm,
+    Back,
+}
+
+// Menus follow the movement bindings plus the arrow keys, so they stay usable
+// even with an unusual layout
+pub fn read_menu_action(
+    actions: &ActionState,
+    keyboard_input: &Input<KeyCode>,
+    gamepads: &Gamepads,
+    buttons: &Input<GamepadButton>,
+) -> Option<MenuAction> {
+    let pad = |button_type| {
+        gamepads
+            .iter()
+            .any(|gamepad| buttons.just_pressed(GamepadButton::new(*gamepad, button_type)))
+    };
+
+    if actions.just_pressed(Action::MoveUp)
+        || keyboard_input.just_pressed(KeyCode::Up)
+        || pad(GamepadButtonType::DPadUp)
+    {
+        Some(MenuAction::Up)
+    } else if actions.just_pressed(Action::MoveDown)
+        || keyboard_input.just_pressed(KeyCode::Down)
+        || pad(GamepadButtonType::DPadDown)
+    {
+        Some(MenuAction::Down)
+    } else if keyboard_input.any_just_pressed([KeyCode::Return, KeyCode::Space])
+        || pad(GamepadButtonType::South)
+    {
+        Some(MenuAction::Confirm)
+    } else if keyboard_input.any_just_pressed([KeyCode::Escape, KeyCode::Back])
+        || pad(GamepadButtonType::East)
+    {
+        Some(MenuAction::Back)
+    } else {
+        None
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn menu_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    actions: Res<ActionState>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    mut menu: ResMut<Menu>,
+    mut bindings: ResMut<KeyBindings>,
+    bindings_path: Res<BindingsPath>,
+    mut app_state: ResMut<State<AppState>>,
+    mut windows: Option<ResMut<Windows>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if let Some(action) = menu.rebinding {
+        // escape cancels, any other key becomes the new binding
+        if let Some(&key) = keyboard_input.get_just_pressed().next() {
+            if key != KeyCode::Escape {
+                bindings.rebind(action, key);
+                bindings_path.save(&bindings);
+            }
+            menu.rebinding = None;
+        }
+        return;
+    }
+
+    let entries = menu.page.entries();
+    match read_menu_action(&actions, &keyboard_input, &gamepads, &buttons) {
+        Some(MenuAction::Up) => {
+            menu.selected = (menu.selected + entries.len() - 1) % entries.len();
+        }
+        Some(MenuAction::Down) => {
+            menu.selected = (menu.selected + 1) % entries.len();
+        }
+        Some(MenuAction::Back) => {
+            menu.page = MenuPage::Main;
+            menu.selected = 0;
+        }
+        Some(MenuAction::Confirm) => match entries[menu.selected] {
+            MenuEntry::Start => app_state.set(AppState::InGame).unwrap(),
+            MenuEntry::Options => {
+                menu.page = MenuPage::Options;
+                menu.selected = 0;
+            }
+            MenuEntry::Trophies => {
+                menu.page = MenuPage::Trophies;
+                menu.selected = 0;
+            }
+            MenuEntry::Quit => exit.send(AppExit),
+            MenuEntry::Fullscreen => {
+                if let Some(window) = windows.as_mut().and_then(|w| w.get_primary_mut()) {
+                    let mode = match window.mode() {
+                        WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+                        _ => WindowMode::Windowed,
+                    };
+                    window.set_mode(mode);
+                }
+                // the entry label shows the window mode, so redraw
+                menu.set_changed();
+            }
+            MenuEntry::Control => {
+                bindings.control_mode = match bindings.control_mode {
+                    ControlMode::Direct => ControlMode::Pointer,
+                    ControlMode::Pointer => ControlMode::Direct,
+                };
+                bindings_path.save(&bindings);
+                menu.set_changed();
+            }
+            MenuEntry::Deadzone => {
+                // step through 0.1..=0.5
+                let next = (bindings.stick_deadzone * 10.).round() as i32 % 5 + 1;
+                bindings.stick_deadzone = next as f32 / 10.;
+                bindings_path.save(&bindings);
+                menu.set_changed();
+            }
+            MenuEntry::Bind(action) => menu.rebinding = Some(action),
+            MenuEntry::Back => {
+                menu.page = MenuPage::Main;
+                menu.selected = 0;
+            }
+        },
+        None => {}
+    }
+}
+
+fn entry_label(
+    entry: MenuEntry,
+    menu: &Menu,
+    windows: &Option<Res<Windows>>,
+    bindings: &KeyBindings,
+) -> String {
+    match entry {
+        MenuEntry::Start => "start".to_string(),
+        MenuEntry::Options => "options".to_string(),
+        MenuEntry::Trophies => "trophies".to_string(),
+        MenuEntry::Quit => "quit".to_string(),
+        MenuEntry::Fullscreen => {
+            let fullscreen = windows
+                .as_ref()
+                .and_then(|w| w.get_primary())
+                .is_some_and(|w| !matches!(w.mode(), WindowMode::Windowed));
+            format!("fullscreen: {}", if fullscreen { "on" } else { "off" })
+        }
+        MenuEntry::Control => match bindings.control_mode {
+            ControlMode::Direct => "control: keys / gamepad".to_string(),
+            ControlMode::Pointer => "control: mouse / touch".to_string(),
+        },
+        MenuEntry::Deadzone => format!("stick deadzone: {:.1}", bindings.stick_deadzone),
+        MenuEntry::Bind(action) if menu.rebinding == Some(action) => {
+            format!("{}: press a key...", action.label())
+        }
+        MenuEntry::Bind(action) => {
+            let keys: Vec<String> = bindings
+                .keys(action)
+                .iter()
+                .map(|k| format!("{:?}", k))
+                .collect();
+            format!("{}: {}", action.label(), keys.join(", "))
+        }
+        MenuEntry::Back => "back".to_string(),
+    }
+}
+
+pub fn spawn_menu_text(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    value: String,
+    color: Color,
+    top: f32,
+    marker: impl Component,
+) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                value,
+                TextStyle {
+                    font: asset_server.load("ProggyClean.ttf"),
+                    font_size: 30.0,
+                    color,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(top),
+                    left: Val::Px(50.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(marker);
+}
+
+// The whole page is rebuilt whenever the selection or page changes
+#[allow(clippy::too_many_arguments)]
+fn draw_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    menu: Res<Menu>,
+    screen: Query<Entity, With<MenuScreen>>,
+    windows: Option<Res<Windows>>,
+    trophy_defs: Res<Assets<TrophyDefs>>,
+    trophy_handle: Res<Handle<TrophyDefs>>,
+    save: Res<SaveData>,
+    bindings: Res<KeyBindings>,
+) {
+    if !menu.is_changed() {
+        return;
+    }
+    for ent in screen.iter() {
+        commands.entity(ent).despawn();
+    }
+
+    let title = match menu.page {
+        MenuPage::Main => "RE-ACTION",
+        MenuPage::Options => "OPTIONS",
+        MenuPage::Trophies => "TROPHIES",
+    };
+    spawn_menu_text(
+        &mut commands,
+        &asset_server,
+        title.to_string(),
+        Color::WHITE,
+        50.,
+        MenuScreen,
+    );
+
+    let mut top = 120.;
+    if menu.page == MenuPage::Trophies {
+        if let Some(defs) = trophy_defs.get(&trophy_handle) {
+            for def in defs.trophies.iter() {
+                let (mark, color) = if save.has_trophy(&def.name) {
+                    ("x", Color::WHITE)
+                } else {
+                    (" ", Color::GRAY)
+                };
+                let line = format!("[{}] {} - {}", mark, def.name, def.description);
+                spawn_menu_text(&mut commands, &asset_server, line, color, top, MenuScreen);
+                top += 40.;
+            }
+        }
+        let best = format!("best score: {}", save.best_score);
+        spawn_menu_text(
+            &mut commands,
+            &asset_server,
+            best,
+            Color::GRAY,
+            top,
+            MenuScreen,
+        );
+        top += 80.;
+    }
+
+    for (i, entry) in menu.page.entries().iter().enumerate() {
+        let label = entry_label(*entry, &menu, &windows, &bindings);
+        let (label, color) = if i == menu.selected {
+            (format!("> {}", label), Color::WHITE)
+        } else {
+            (format!("  {}", label), Color::GRAY)
+        };
+        spawn_menu_text(&mut commands, &asset_server, label, color, top, MenuScreen);
+        top += 40.;
+    }
+}
+
+fn close_menu(
+    mut commands: Commands,
+    mut menu: ResMut<Menu>,
+    screen: Query<Entity, With<MenuScreen>>,
+) {
+    for ent in screen.iter() {
+        commands.entity(ent).despawn();
+    }
+    menu.page = MenuPage::Main;
+    menu.selected = 0;
+    menu.rebinding = None;
+}
+
+// everything the HUD is made of, hidden while a menu is up
+type HudQuery<'w, 's> = Query<'w, 's, &'static mut Visibility, Or<(With<UIElement>, With<Trophy>)>>;
+
+// The HUD is spawned at startup; keep it hidden behind the menu. This runs every
+// frame because the trophy row appears whenever its asset finishes loading.
+fn hide_hud(mut hud: HudQuery) {
+    for mut visibility in hud.iter_mut() {
+        visibility.is_visible = false;
+    }
+}
+
+fn show_hud(mut hud: HudQuery) {
+    for mut visibility in hud.iter_mut() {
+        visibility.is_visible = true;
+    }
+}
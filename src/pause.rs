@@ -0,0 +1,185 @@
+use bevy::{app::AppExit, prelude::*};
+
+use crate::actions::{Action, ActionState};
+use crate::menu::spawn_menu_text;
+use crate::{on_fixed_update, AppState, FixedUpdateStage};
+
+pub struct PausePlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseEntry {
+    Resume,
+    Restart,
+    Quit,
+}
+
+const PAUSE_ENTRIES: [PauseEntry; 3] = [PauseEntry::Resume, PauseEntry::Restart, PauseEntry::Quit];
+
+struct PauseMenu {
+    selected: usize,
+}
+
+// actions held on the previous tick, so a press is only acted on once
+struct HeldActions(Vec<Action>);
+
+#[derive(Component)]
+struct PauseScreen;
+
+#[derive(Component)]
+struct PauseEntryText;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub struct PauseInput;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PauseMenu { selected: 0 })
+            .insert_resource(HeldActions(Vec::new()))
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                on_fixed_update(AppState::InGame).with_system(pause_game.label(PauseInput)),
+            )
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                on_fixed_update(AppState::Paused).with_system(pause_menu_input.label(PauseInput)),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(spawn_overlay))
+            .add_system_set(SystemSet::on_update(AppState::Paused).with_system(draw_pause_entries))
+            .add_system_set(SystemSet::on_exit(AppState::Paused).with_system(despawn_overlay));
+    }
+}
+
+// Paused is pushed on top of InGame, so every InGame system (movement, collisions,
+// spawning, storyline) stops and the world stays exactly as it was.
+// Like the teleport, the press is edge-detected per tick so replays pause on the same tick.
+fn pause_game(
+    actions: Res<ActionState>,
+    mut app_state: ResMut<State<AppState>>,
+    mut menu: ResMut<PauseMenu>,
+    mut held: ResMut<HeldActions>,
+    mut pause_held: Local<bool>,
+) {
+    let pause = actions.pressed(Action::Pause) && !*pause_held;
+    *pause_held = actions.pressed(Action::Pause);
+
+    if pause {
+        menu.selected = 0;
+        // don't let the pause menu see the same press as a resume
+        held.0 = held_actions(&actions);
+        app_state.push(AppState::Paused).unwrap();
+    }
+}
+
+fn held_actions(actions: &ActionState) -> Vec<Action> {
+    Action::ALL
+        .iter()
+        .copied()
+        .filter(|a| actions.pressed(*a))
+        .collect()
+}
+
+fn spawn_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .insert(PauseScreen);
+
+    spawn_menu_text(
+        &mut commands,
+        &asset_server,
+        "PAUSED".to_string(),
+        Color::WHITE,
+        150.,
+        PauseScreen,
+    );
+}
+
+// Only actions are read here, not keys, so resuming from the pause menu
+// ends up in a recording like the rest of the input
+fn pause_menu_input(
+    actions: Res<ActionState>,
+    mut menu: ResMut<PauseMenu>,
+    mut held: ResMut<HeldActions>,
+    mut app_state: ResMut<State<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let previous = std::mem::replace(&mut held.0, held_actions(&actions));
+    let tapped = |action| held.0.contains(&action) && !previous.contains(&action);
+
+    let entry = if tapped(Action::MoveUp) {
+        menu.selected = (menu.selected + PAUSE_ENTRIES.len() - 1) % PAUSE_ENTRIES.len();
+        None
+    } else if tapped(Action::MoveDown) {
+        menu.selected = (menu.selected + 1) % PAUSE_ENTRIES.len();
+        None
+    } else if tapped(Action::Confirm) {
+        Some(PAUSE_ENTRIES[menu.selected])
+    } else if tapped(Action::Pause) {
+        Some(PauseEntry::Resume)
+    } else {
+        None
+    };
+
+    match entry {
+        Some(PauseEntry::Resume) => app_state.pop().unwrap(),
+        // replacing the whole stack exits InGame too, so the run is torn down
+        // and InGame is entered again from scratch
+        Some(PauseEntry::Restart) => app_state.replace(AppState::InGame).unwrap(),
+        Some(PauseEntry::Quit) => exit.send(AppExit),
+        None => {}
+    }
+}
+
+fn draw_pause_entries(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    menu: Res<PauseMenu>,
+    entries: Query<Entity, With<PauseEntryText>>,
+) {
+    if !menu.is_changed() && !entries.is_empty() {
+        return;
+    }
+    for ent in entries.iter() {
+        commands.entity(ent).despawn();
+    }
+
+    let mut top = 250.;
+    for (i, entry) in PAUSE_ENTRIES.iter().enumerate() {
+        let label = match entry {
+            PauseEntry::Resume => "resume",
+            PauseEntry::Restart => "restart",
+            PauseEntry::Quit => "quit",
+        };
+        let (label, color) = if i == menu.selected {
+            (format!("> {}", label), Color::WHITE)
+        } else {
+            (format!("  {}", label), Color::GRAY)
+        };
+        spawn_menu_text(
+            &mut commands,
+            &asset_server,
+            label,
+            color,
+            top,
+            PauseEntryText,
+        );
+        top += 50.;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_overlay(
+    mut commands: Commands,
+    screen: Query<Entity, Or<(With<PauseScreen>, With<PauseEntryText>)>>,
+) {
+    for ent in screen.iter() {
+        commands.entity(ent).despawn();
+    }
+}
@@ -0,0 +1,244 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::rng::GameRng;
+
+// Where a new thingy may appear. The spawn area already keeps clear of the
+// HUD and trophy row above the field, `avoid` can rule out more.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PlacementRules {
+    // so a thingy can't appear on top of the ball and be collected for free
+    pub min_ball_distance: f32,
+    pub max_ball_distance: Option<f32>,
+    // keeps thingies from piling up on each other
+    pub min_thingy_distance: f32,
+    pub avoid: Vec<Area>,
+    // random tries before settling for the best one found
+    pub attempts: u32,
+}
+
+impl Default for PlacementRules {
+    fn default() -> Self {
+        PlacementRules {
+            min_ball_distance: 100.,
+            max_ball_distance: None,
+            min_thingy_distance: 40.,
+            avoid: Vec::new(),
+            attempts: 20,
+        }
+    }
+}
+
+// World space rectangle
+#[derive(Debug, Clone, Deserialize)]
+pub struct Area {
+    pub left: f32,
+    pub bottom: f32,
+    pub right: f32,
+    pub top: f32,
+}
+
+impl Area {
+    fn contains(&self, p: Vec2) -> bool {
+        p.x >= self.left && p.x <= self.right && p.y >= self.bottom && p.y <= self.top
+    }
+
+    pub fn grown(&self, by: f32) -> Area {
+        Area {
+            left: self.left - by,
+            bottom: self.bottom - by,
+            right: self.right + by,
+            top: self.top + by,
+        }
+    }
+}
+
+impl PlacementRules {
+    // How many of the rules `p` breaks, `covered` is avoided like `avoid`
+    fn broken_rules(&self, p: Vec2, ball: Option<Vec2>, others: &[Vec2], covered: &[Area]) -> u32 {
+        let mut broken = 0;
+        if let Some(ball) = ball {
+            let d = ball.distance(p);
+            if d < self.min_ball_distance {
+                broken += 1;
+            }
+            if self.max_ball_distance.is_some_and(|max| d > max) {
+                broken += 1;
+            }
+        }
+        if others
+            .iter()
+            .any(|o| o.distance(p) < self.min_thingy_distance)
+        {
+            broken += 1;
+        }
+        if self.avoid.iter().chain(covered).any(|a| a.contains(p)) {
+            broken += 1;
+        }
+        broken
+    }
+
+    // Picks a spot inside `lower..upper` that follows the rules and is outside
+    // `covered`. When none of the attempts does, the first one that breaks the
+    // fewest rules is used, so a thingy always spawns.
+    pub fn place(
+        &self,
+        rng: &mut GameRng,
+        lower: Vec2,
+        upper: Vec2,
+        ball: Option<Vec2>,
+        others: &[Vec2],
+        covered: &[Area],
+    ) -> Vec2 {
+        let mut fallback: Option<(Vec2, u32)> = None;
+        for _ in 0..self.attempts.max(1) {
+            let p = Vec2::new(
+                between(rng, lower.x, upper.x),
+                between(rng, lower.y, upper.y),
+            );
+            let broken = self.broken_rules(p, ball, others, covered);
+            if broken == 0 {
+                return p;
+            }
+            if fallback.is_none_or(|(_, fewest)| broken < fewest) {
+                fallback = Some((p, broken));
+            }
+        }
+        fallback.map_or((lower + upper) / 2., |(p, _)| p)
+    }
+}
+
+// gen_range panics on an empty range, so a squashed spawn area uses its middle
+fn between(rng: &mut GameRng, low: f32, high: f32) -> f32 {
+    if low < high {
+        rng.rng.gen_range(low..high)
+    } else {
+        (low + high) / 2.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOWER: Vec2 = Vec2::new(-300., -200.);
+    const UPPER: Vec2 = Vec2::new(300., 200.);
+
+    fn rules() -> PlacementRules {
+        PlacementRules {
+            min_ball_distance: 0.,
+            max_ball_distance: None,
+            min_thingy_distance: 0.,
+            avoid: Vec::new(),
+            attempts: 200,
+        }
+    }
+
+    fn place_seeded(rules: &PlacementRules, ball: Option<Vec2>, others: &[Vec2]) -> Vec<Vec2> {
+        (0..50)
+            .map(|seed| {
+                let mut rng = GameRng::new(Some(seed));
+                rules.place(&mut rng, LOWER, UPPER, ball, others, &[])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_within_ball_distances() {
+        let rules = PlacementRules {
+            min_ball_distance: 100.,
+            max_ball_distance: Some(150.),
+            ..rules()
+        };
+        let ball = Vec2::new(50., -20.);
+        for p in place_seeded(&rules, Some(ball), &[]) {
+            let d = p.distance(ball);
+            assert!((100. ..=150.).contains(&d), "{} is {} from the ball", p, d);
+        }
+    }
+
+    #[test]
+    fn stays_out_of_avoided_areas() {
+        let rules = PlacementRules {
+            avoid: vec![
+                Area {
+                    left: -300.,
+                    bottom: -200.,
+                    right: 0.,
+                    top: 200.,
+                },
+                Area {
+                    left: 0.,
+                    bottom: 100.,
+                    right: 300.,
+                    top: 200.,
+                },
+            ],
+            ..rules()
+        };
+        for p in place_seeded(&rules, None, &[]) {
+            assert!(p.x > 0. && p.y < 100., "{} is in an avoided area", p);
+        }
+    }
+
+    #[test]
+    fn stays_out_of_covered_areas() {
+        let covered = [Area {
+            left: -300.,
+            bottom: -200.,
+            right: 200.,
+            top: 200.,
+        }];
+        for seed in 0..50 {
+            let mut rng = GameRng::new(Some(seed));
+            let p = rules().place(&mut rng, LOWER, UPPER, None, &[], &covered);
+            assert!(p.x > 200., "{} is under the covered area", p);
+        }
+    }
+
+    #[test]
+    fn spaces_out_thingies() {
+        let rules = PlacementRules {
+            min_thingy_distance: 80.,
+            ..rules()
+        };
+        let others = [Vec2::ZERO, Vec2::new(-150., 60.), Vec2::new(200., -100.)];
+        for p in place_seeded(&rules, None, &others) {
+            for o in others {
+                assert!(
+                    p.distance(o) >= 80.,
+                    "{} is {} from {}",
+                    p,
+                    p.distance(o),
+                    o
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_fewest_broken_rules() {
+        // every spot is too close to the thingy at the ball, so only the ball
+        // distance can still be kept
+        let rules = PlacementRules {
+            max_ball_distance: Some(100.),
+            min_thingy_distance: 1000.,
+            ..rules()
+        };
+        let ball = Vec2::ZERO;
+        for p in place_seeded(&rules, Some(ball), &[ball]) {
+            assert!(p.distance(ball) <= 100., "{} is too far from the ball", p);
+            assert!(p.cmpge(LOWER).all() && p.cmple(UPPER).all());
+        }
+    }
+
+    #[test]
+    fn places_in_an_empty_area() {
+        let mut rng = GameRng::new(Some(7));
+        let corner = Vec2::new(10., -5.);
+        let p = rules().place(&mut rng, corner, corner - Vec2::ONE, None, &[], &[]);
+        assert_eq!(p, corner - Vec2::splat(0.5));
+    }
+}
@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+
+// The arena between the two gray lines. The ball is kept inside it and
+// thingies spawn inside it.
+pub struct PlayField {
+    pub min: Vec2,
+    pub max: Vec2,
+    // share of the speed kept when the ball bounces off a wall, 0 stops it dead
+    pub restitution: f32,
+}
+
+impl PlayField {
+    pub fn from_window(width: f32, height: f32) -> Self {
+        PlayField {
+            min: Vec2::new(-(width / 2.) + 20., -(height / 2.) + 40.),
+            max: Vec2::new((width / 2.) - 20., (height / 2.) - 40.),
+            restitution: 0.5,
+        }
+    }
+
+    // Where thingies may appear: away from the walls, with room above
+    // for the storyline text
+    pub fn spawn_area(&self) -> (Vec2, Vec2) {
+        (
+            self.min + Vec2::new(60., 20.),
+            self.max - Vec2::new(60., 70.),
+        )
+    }
+}
@@ -0,0 +1,62 @@
+use bevy::{input::touch::Touches, prelude::*};
+
+use crate::actions::{ActionState, ControlMode, KeyBindings, ReadActions};
+use crate::components::{Ball, BallPreset};
+use crate::TIMESTEP;
+
+pub struct PointerPlugin;
+
+impl Plugin for PointerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PreUpdate, follow_pointer.after(ReadActions));
+    }
+}
+
+// Turn the cursor or touch point into a stick direction towards it. Going through
+// the stick keeps the speed capped the same way and lets replays record it.
+fn follow_pointer(
+    bindings: Res<KeyBindings>,
+    windows: Option<Res<Windows>>,
+    touches: Res<Touches>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    ball: Query<&Transform, With<Ball>>,
+    preset: Res<BallPreset>,
+    mut actions: ResMut<ActionState>,
+) {
+    if bindings.control_mode != ControlMode::Pointer || actions.replaying {
+        return;
+    }
+    let window = match windows.as_ref().and_then(|w| w.get_primary()) {
+        Some(w) => w,
+        None => return,
+    };
+    let window_size = Vec2::new(window.width(), window.height());
+
+    // a touch wins over the cursor; touch positions have their origin at the top
+    let screen_pos = match touches.iter().next() {
+        Some(touch) => Vec2::new(touch.position().x, window_size.y - touch.position().y),
+        None => match window.cursor_position() {
+            Some(pos) => pos,
+            None => return,
+        },
+    };
+
+    let (camera, camera_transform) = match camera.get_single() {
+        Ok(c) => c,
+        Err(_) => return,
+    };
+    let ball = match ball.get_single() {
+        Ok(b) => b,
+        Err(_) => return,
+    };
+
+    let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    let target = ndc_to_world.project_point3(ndc.extend(-1.0)).truncate();
+
+    // full speed until the ball is within one tick of the target, then slow down
+    // so it stops on it rather than overshooting
+    let max_step = preset.max_speed * TIMESTEP as f32;
+    let direction = (target - ball.translation.truncate()) / max_step;
+    actions.set_stick(direction.clamp_length_max(1.));
+}
@@ -0,0 +1,166 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::actions::{ActionSnapshot, ActionState};
+use crate::ball::BallSystem;
+use crate::pause::PauseInput;
+use crate::{on_fixed_update, AppState, FixedUpdateStage, RestartInput};
+
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub seed: u64,
+    ticks: Vec<ActionSnapshot>,
+}
+
+impl Replay {
+    // File format: a `seed <n>` header followed by run-length encoded
+    // `<tick count> <action mask> <stick x> <stick y>` lines, the mask is a bit
+    // per `Action::ALL` entry and the stick axes are -127..=127.
+    // Actions are stored rather than keys so a replay plays back the same
+    // whatever the key bindings are.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let seed = lines
+            .next()
+            .and_then(|l| l.strip_prefix("seed "))
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid("missing seed header"))?;
+
+        let mut ticks = Vec::new();
+        for line in lines {
+            let fields: Vec<&str> = line.split(' ').collect();
+            if fields.len() != 4 {
+                return Err(invalid("malformed tick line"));
+            }
+            let count: usize = fields[0].parse().map_err(|_| invalid("bad tick count"))?;
+            let mask: u32 = fields[1].parse().map_err(|_| invalid("bad action mask"))?;
+            let x: i8 = fields[2].parse().map_err(|_| invalid("bad stick axis"))?;
+            let y: i8 = fields[3].parse().map_err(|_| invalid("bad stick axis"))?;
+            let snapshot = ActionSnapshot {
+                mask,
+                stick: (x, y),
+            };
+            ticks.extend(std::iter::repeat_n(snapshot, count));
+        }
+
+        Ok(Replay { seed, ticks })
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = fs::File::create(path)?;
+        writeln!(file, "seed {}", self.seed)?;
+
+        let mut ticks = self.ticks.iter().peekable();
+        while let Some(&snapshot) = ticks.next() {
+            let mut count = 1;
+            while ticks.next_if_eq(&&snapshot).is_some() {
+                count += 1;
+            }
+            let (x, y) = snapshot.stick;
+            writeln!(file, "{} {} {} {}", count, snapshot.mask, x, y)?;
+        }
+        Ok(())
+    }
+}
+
+// Input is sampled once per fixed tick, before the ball moves, so a replay
+// reproduces the simulation no matter how many ticks each frame ran.
+#[derive(Clone)]
+pub enum ReplayPlugin {
+    Record { path: PathBuf, seed: u64 },
+    Playback(Replay),
+}
+
+struct Recording {
+    path: PathBuf,
+    replay: Replay,
+}
+
+struct Playback {
+    replay: Replay,
+    tick: usize,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        match self {
+            ReplayPlugin::Record { path, seed } => {
+                app.insert_resource(Recording {
+                    path: path.clone(),
+                    replay: Replay {
+                        seed: *seed,
+                        ticks: Vec::new(),
+                    },
+                })
+                .add_system_set_to_stage(FixedUpdateStage, in_run(AppState::InGame, record_input))
+                .add_system_set_to_stage(FixedUpdateStage, in_run(AppState::Paused, record_input))
+                .add_system_set_to_stage(FixedUpdateStage, in_run(AppState::GameOver, record_input))
+                .add_system_to_stage(CoreStage::Last, save_recording);
+            }
+            ReplayPlugin::Playback(replay) => {
+                app.insert_resource(Playback {
+                    replay: replay.clone(),
+                    tick: 0,
+                })
+                .add_system_set_to_stage(FixedUpdateStage, in_run(AppState::InGame, playback_input))
+                .add_system_set_to_stage(FixedUpdateStage, in_run(AppState::Paused, playback_input))
+                .add_system_set_to_stage(
+                    FixedUpdateStage,
+                    in_run(AppState::GameOver, playback_input),
+                );
+            }
+        }
+    }
+}
+
+// Ticks are taken in play, paused and on the game over screen, where the
+// run can be restarted. The main menu is never reached while recording.
+fn in_run<Params>(
+    state: AppState,
+    system: impl ParallelSystemDescriptorCoercion<Params>,
+) -> SystemSet {
+    on_fixed_update(state).with_system(
+        system
+            .before(BallSystem::Movement)
+            .before(PauseInput)
+            .before(RestartInput),
+    )
+}
+
+fn record_input(actions: Res<ActionState>, mut recording: ResMut<Recording>) {
+    recording.replay.ticks.push(actions.snapshot());
+}
+
+fn save_recording(mut ev_exit: EventReader<AppExit>, recording: Res<Recording>) {
+    if ev_exit.iter().next().is_some() {
+        match recording.replay.save(&recording.path) {
+            Ok(()) => println!("replay saved to {}", recording.path.display()),
+            Err(e) => eprintln!("could not save replay: {}", e),
+        }
+    }
+}
+
+// Once the replay runs out the player's own input takes over, so they can
+// quit or carry on playing
+fn playback_input(mut actions: ResMut<ActionState>, mut playback: ResMut<Playback>) {
+    let snapshot = match playback.replay.ticks.get(playback.tick) {
+        Some(snapshot) => *snapshot,
+        None => {
+            if actions.replaying {
+                println!("replay finished");
+                actions.replaying = false;
+            }
+            return;
+        }
+    };
+    playback.tick += 1;
+
+    actions.replaying = true;
+    actions.apply(snapshot);
+}
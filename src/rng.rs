@@ -0,0 +1,23 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// All game randomness goes through this so a run can be reproduced from its seed
+pub struct GameRng {
+    pub seed: u64,
+    pub rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+        GameRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // A separate generator from the same seed, for randomness that only
+    // changes how things look and so mustn't shift the gameplay stream
+    pub fn side_stream(&self, salt: u64) -> StdRng {
+        StdRng::seed_from_u64(self.seed ^ salt)
+    }
+}
@@ -0,0 +1,216 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, Score};
+
+// bump when the layout changes and add a migration to `SaveData::load`
+const SAVE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub unlocked_trophies: Vec<String>,
+    pub best_score: i32,
+    pub total_kills: u32,
+    pub play_count: u32,
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        SaveData {
+            version: SAVE_VERSION,
+            unlocked_trophies: Vec::new(),
+            best_score: 0,
+            total_kills: 0,
+            play_count: 0,
+        }
+    }
+}
+
+impl SaveData {
+    fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let data: SaveData = serde_json::from_str(&contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if data.version > SAVE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "save file version {} is newer than this build",
+                    data.version
+                ),
+            ));
+        }
+        Ok(data)
+    }
+
+    fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, contents)
+    }
+
+    pub fn has_trophy(&self, name: &str) -> bool {
+        self.unlocked_trophies.iter().any(|t| t == name)
+    }
+
+    pub fn unlock_trophy(&mut self, name: &str) {
+        if !self.has_trophy(name) {
+            self.unlocked_trophies.push(name.to_string());
+        }
+    }
+}
+
+// the game's directory inside the user's data directory
+pub fn data_dir() -> Option<PathBuf> {
+    let data_dir = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|h| PathBuf::from(h).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+    };
+    data_dir.map(|d| d.join("reaction-bevy"))
+}
+
+// Where the save lives, None when the run shouldn't touch the player's save (headless)
+struct SavePath(Option<PathBuf>);
+
+pub struct SavePlugin {
+    pub persist: bool,
+}
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        let path = if self.persist {
+            data_dir().map(|d| d.join("save.json"))
+        } else {
+            None
+        };
+        let (data, path) = open_save(path);
+
+        app.insert_resource(data)
+            .insert_resource(SavePath(path))
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(count_play))
+            // InGame is exited on game over and on restarting from the pause menu
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(write_save))
+            .add_system_to_stage(CoreStage::Last, write_save_on_exit);
+    }
+}
+
+// Loads the save at `path`. A file that can't be loaded (corrupt, or written
+// by a newer build) is moved aside to `save.json.bak` so the fresh save
+// doesn't overwrite it, and when that fails the run leaves the save alone.
+fn open_save(path: Option<PathBuf>) -> (SaveData, Option<PathBuf>) {
+    let p = match path {
+        Some(p) if p.exists() => p,
+        path => return (SaveData::default(), path),
+    };
+    match SaveData::load(&p) {
+        Ok(data) => (data, Some(p)),
+        Err(e) => {
+            eprintln!("could not load save file {}: {}", p.display(), e);
+            let backup = p.with_extension("json.bak");
+            match fs::rename(&p, &backup) {
+                Ok(()) => {
+                    eprintln!("moved it to {}", backup.display());
+                    (SaveData::default(), Some(p))
+                }
+                Err(e) => {
+                    eprintln!("could not move it aside, not saving this session: {}", e);
+                    (SaveData::default(), None)
+                }
+            }
+        }
+    }
+}
+
+fn count_play(mut save: ResMut<SaveData>) {
+    save.play_count += 1;
+}
+
+fn write_save(mut save: ResMut<SaveData>, score: Res<Score>, path: Res<SavePath>) {
+    save.best_score = save.best_score.max(score.0);
+    if let Some(path) = &path.0 {
+        if let Err(e) = save.write(path) {
+            eprintln!("could not write save file {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn write_save_on_exit(
+    mut ev_exit: EventReader<AppExit>,
+    save: ResMut<SaveData>,
+    score: Res<Score>,
+    path: Res<SavePath>,
+) {
+    if ev_exit.iter().next().is_some() {
+        write_save(save, score, path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh directory per test so they can run in parallel
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("reaction-bevy-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn loads_a_current_save() {
+        let dir = scratch_dir("current");
+        let path = dir.join("save.json");
+        let mut data = SaveData {
+            best_score: 150,
+            ..default()
+        };
+        data.unlock_trophy("collector");
+        data.write(&path).unwrap();
+
+        let (loaded, kept) = open_save(Some(path.clone()));
+        assert_eq!(loaded.best_score, 150);
+        assert!(loaded.has_trophy("collector"));
+        assert_eq!(kept, Some(path));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_a_save_from_a_newer_build() {
+        let dir = scratch_dir("newer");
+        let path = dir.join("save.json");
+        let newer = r#"{"version": 99, "unlocked_trophies": ["collector"], "best_score": 150,
+            "total_kills": 12, "play_count": 3}"#;
+        fs::write(&path, newer).unwrap();
+        assert!(SaveData::load(&path).is_err());
+
+        let (loaded, kept) = open_save(Some(path.clone()));
+        assert_eq!(loaded.best_score, 0);
+        assert_eq!(kept, Some(path.clone()));
+        assert!(!path.exists());
+        let backup = dir.join("save.json.bak");
+        assert_eq!(fs::read_to_string(backup).unwrap(), newer);
+
+        // writing this session's save leaves the moved file alone
+        loaded.write(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("save.json.bak")).unwrap(),
+            newer
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,30 +1,285 @@
+use std::collections::HashMap;
+
 use crate::{
-    components::{StoryLines, TextLine, Thingy},
-    Score,
+    actions::{Action, ActionState},
+    components::{Spawned, StoryLines, TextLine, Thingy},
+    on_fixed_update,
+    placement::Area,
+    rng::GameRng,
+    save::SaveData,
+    thingy::ScoreKills,
+    AppState, CollisionEvent, FixedUpdateStage, Score, StartScore, TIMESTEP,
 };
 use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use reaction_story::{
+    LineEffects, Story, StoryCondition, StoryNode, STORY_FONT_SIZE, STORY_TEXT_HEIGHT,
+    STORY_TEXT_WIDTH,
+};
+
+// gap between a thingy and the storyline text hanging above it
+const TEXT_OFFSET: f32 = 15.;
 
-pub struct StoryLinePlugin;
+// stand-ins for glitched characters
+const GLITCH_CHARS: &[char] = &['#', '%', '$', '&', '@', '!', '?', '*', '4', '5', '2'];
+// glitched characters are scrambled anew this often, in fixed ticks
+const GLITCH_TICKS: u32 = 6;
+// picks the glitch stream out of the run's seed
+const GLITCH_SALT: u64 = 0x6c69_6e65;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub struct AdvanceStory;
+
+pub struct StoryLinePlugin {
+    // storyline file inside the assets folder
+    pub path: String,
+}
+
+struct StoryFile(String);
 
 impl Plugin for StoryLinePlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system_to_stage(StartupStage::PreStartup, setup_storylines)
-            .add_system(draw_storyline);
+        app.insert_resource(StoryFile(self.path.clone()))
+            .insert_resource(StoryGraph::default())
+            .insert_resource(StoryState::default())
+            .add_startup_system_to_stage(StartupStage::PreStartup, setup_storylines)
+            .add_startup_system(seed_glitches)
+            .add_system(build_story_graph)
+            .add_system_set(
+                SystemSet::on_enter(AppState::InGame)
+                    .with_system(spawn_textline)
+                    .with_system(reset_story),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(note_pause))
+            // not run while paused so the current line stays on screen
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                on_fixed_update(AppState::InGame)
+                    .with_system(advance_story.label(AdvanceStory).after(ScoreKills)),
+            )
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(draw_storyline));
+    }
+}
+
+// The loaded story with node ids resolved to indices
+#[derive(Default)]
+struct StoryGraph {
+    nodes: Vec<StoryNode>,
+    next: Vec<Vec<usize>>,
+    start: Vec<usize>,
+}
+
+// How far this run has got through the story
+#[derive(Default)]
+pub struct StoryState {
+    // nodes that may be shown next, None until the story has started
+    frontier: Option<Vec<usize>>,
+    current: Option<ShownLine>,
+    seconds: f32,
+    idle_seconds: f32,
+    paused: bool,
+    ticks: u32,
+    // seeds this tick's scramble of glitched characters
+    glitch: u64,
+}
+
+// Glitches draw from their own stream so the text never touches the
+// gameplay randomness, and a replay scrambles the same characters
+struct GlitchRng(StdRng);
+
+// The line on screen
+struct ShownLine {
+    node: usize,
+    // the score and story time it appeared at
+    score: i32,
+    since: f32,
+    // when it started to leave: once the score has moved on and its
+    // minimum duration is up
+    until: Option<f32>,
+    // the thingy it hangs above, and where that was last seen
+    anchor: Option<Entity>,
+    position: Option<Vec2>,
+}
+
+impl StoryGraph {
+    fn from_story(story: &Story) -> Self {
+        let mut nodes = story.nodes.clone();
+        let mut start = story.start.clone();
+        if nodes.is_empty() {
+            nodes = migrate_flat(story);
+            start.clear();
+        }
+
+        let ids: HashMap<&str, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.id.as_str(), i))
+            .collect();
+        let resolve = |id: &String| {
+            let index = ids.get(id.as_str()).copied();
+            if index.is_none() {
+                eprintln!("unknown storyline node: {}", id);
+            }
+            index
+        };
+
+        let next = nodes
+            .iter()
+            .map(|n| n.next.iter().filter_map(resolve).collect())
+            .collect();
+        let mut start: Vec<usize> = start.iter().filter_map(resolve).collect();
+        if start.is_empty() && !nodes.is_empty() {
+            start.push(0);
+        }
+        StoryGraph { nodes, next, start }
+    }
+
+    // Where a run starting at `score` enters the story: lines waiting for a
+    // lower score are already behind it and are skipped rather than shown
+    // one after another
+    fn start_from(&self, score: i32) -> Vec<usize> {
+        let mut frontier = self.start.clone();
+        // bounded so a loop in the graph can't hang
+        for _ in 0..self.nodes.len() {
+            let passed = frontier
+                .iter()
+                .copied()
+                .find(|&i| self.nodes[i].when.min_score.is_some_and(|s| s < score));
+            match passed {
+                Some(i) => frontier = self.next[i].clone(),
+                None => break,
+            }
+        }
+        frontier
+    }
+
+    fn index_of(&self, id: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.id == id)
+    }
+}
+
+// The flat list becomes a chain in score order. Each line waits for its
+// score to be reached, so a line isn't lost when the score skips past it.
+// Ids come from `appears_at` rather than the position in the list, so a
+// reloaded file still finds its place after lines are added or removed.
+fn migrate_flat(story: &Story) -> Vec<StoryNode> {
+    let mut lines: Vec<_> = story.storylines.iter().collect();
+    lines.sort_by_key(|l| l.appears_at);
+    let mut seen: HashMap<i32, usize> = HashMap::new();
+    let ids: Vec<String> = lines
+        .iter()
+        .map(|l| {
+            let n = seen.entry(l.appears_at).or_insert(0);
+            let id = match *n {
+                0 => format!("at{}", l.appears_at),
+                n => format!("at{}.{}", l.appears_at, n),
+            };
+            *n += 1;
+            id
+        })
+        .collect();
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, l)| StoryNode {
+            id: ids[i].clone(),
+            line: l.line.clone(),
+            when: StoryCondition {
+                min_score: Some(l.appears_at),
+                ..default()
+            },
+            next: ids.get(i + 1).cloned().into_iter().collect(),
+            effects: l.effects.clone(),
+        })
+        .collect()
+}
+
+impl StoryState {
+    // Carries the run on from the same nodes in an edited story, looked up by
+    // id. When the nodes it was waiting on are gone it picks up again from
+    // where `score` is, like a run starting there.
+    fn carry_over(&mut self, old: &StoryGraph, new: &StoryGraph, score: i32) {
+        let moved = |i: usize| new.index_of(&old.nodes[i].id);
+        self.frontier = self.frontier.take().map(|f| {
+            let waiting = !f.is_empty();
+            let f: Vec<usize> = f.into_iter().filter_map(moved).collect();
+            if waiting && f.is_empty() {
+                new.start_from(score)
+            } else {
+                f
+            }
+        });
+        self.current = self.current.take().and_then(|mut c| {
+            c.node = moved(c.node)?;
+            Some(c)
+        });
+    }
+
+    // The storyline text on screen, for keeping new thingies out from under it
+    pub fn text_area(&self) -> Option<Area> {
+        self.current.as_ref()?.position.map(text_area)
+    }
+
+    fn allows(&self, when: &StoryCondition, score: i32, save: &SaveData) -> bool {
+        when.min_score.is_none_or(|s| score >= s)
+            && when.max_score.is_none_or(|s| score <= s)
+            && when.min_seconds.is_none_or(|s| self.seconds >= s)
+            && when.max_seconds.is_none_or(|s| self.seconds <= s)
+            && when.trophies.iter().all(|t| save.has_trophy(t))
+            && when.paused.is_none_or(|p| self.paused == p)
+            && when.idle_seconds.is_none_or(|s| self.idle_seconds >= s)
     }
 }
 
-fn setup_storylines(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup_storylines(mut commands: Commands, asset_server: Res<AssetServer>, file: Res<StoryFile>) {
     // load storylines
-    let handle: Handle<StoryLines> = asset_server.load("storylines.json");
+    let handle: Handle<StoryLines> = asset_server.load(file.0.as_str());
     commands.insert_resource(handle);
+}
+
+fn build_story_graph(
+    mut ev_asset: EventReader<AssetEvent<StoryLines>>,
+    assets: Res<Assets<StoryLines>>,
+    handle: Res<Handle<StoryLines>>,
+    mut graph: ResMut<StoryGraph>,
+    mut story: ResMut<StoryState>,
+    score: Res<Score>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Created { handle: h } | AssetEvent::Modified { handle: h } = ev {
+            if *h != *handle {
+                continue;
+            }
+            if let Some(lines) = assets.get(h) {
+                let new = StoryGraph::from_story(&lines.0);
+                story.carry_over(&graph, &new, score.0);
+                *graph = new;
+            }
+        }
+    }
+}
+
+fn seed_glitches(mut commands: Commands, rng: Res<GameRng>) {
+    commands.insert_resource(GlitchRng(rng.side_stream(GLITCH_SALT)));
+}
+
+fn reset_story(mut story: ResMut<StoryState>) {
+    *story = StoryState::default();
+}
+
+fn note_pause(mut story: ResMut<StoryState>) {
+    story.paused = true;
+}
+
+fn spawn_textline(mut commands: Commands, asset_server: Res<AssetServer>) {
     let font = asset_server.load("ProggyClean.ttf");
     let text_style = TextStyle {
         font,
-        font_size: 15.,
+        font_size: STORY_FONT_SIZE,
         color: Color::GRAY,
     };
     let position = Vec2::new(0., 0.);
-    let size = Vec2::new(150., 200.);
+    let size = Vec2::new(STORY_TEXT_WIDTH, STORY_TEXT_HEIGHT);
 
     commands
         .spawn_bundle(Text2dBundle {
@@ -44,28 +299,386 @@ fn setup_storylines(mut commands: Commands, asset_server: Res<AssetServer>) {
         .insert(TextLine);
 }
 
-fn draw_storyline(
-    levels: Res<Assets<StoryLines>>,
-    handles: Res<Handle<StoryLines>>,
+type AnchorQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static Transform, &'static Spawned), With<Thingy>>;
+
+// The story moves on in fixed ticks, so a replay shows the same lines at the
+// same time and thingies are kept out from under them in the same places
+#[allow(clippy::too_many_arguments)]
+fn advance_story(
+    graph: Res<StoryGraph>,
+    mut story: ResMut<StoryState>,
+    actions: Res<ActionState>,
+    save: Res<SaveData>,
     score: Res<Score>,
-    thingy: Query<&Transform, (With<Thingy>, Without<TextLine>)>,
+    start_score: Res<StartScore>,
+    mut glitch_rng: ResMut<GlitchRng>,
+    mut ev: EventReader<CollisionEvent>,
+    thingies: AnchorQuery,
+) {
+    let dt = TIMESTEP as f32;
+    story.seconds += dt;
+    if story.ticks.is_multiple_of(GLITCH_TICKS) {
+        story.glitch = glitch_rng.0.gen();
+    }
+    story.ticks += 1;
+    let moving = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+    ]
+    .iter()
+    .any(|a| actions.pressed(*a))
+        || actions.stick() != Vec2::ZERO;
+    story.idle_seconds = if moving { 0. } else { story.idle_seconds + dt };
+
+    if story.frontier.is_none() && !graph.nodes.is_empty() {
+        story.frontier = Some(graph.start_from(start_score.0));
+    }
+    let next = story.frontier.as_ref().and_then(|frontier| {
+        frontier
+            .iter()
+            .copied()
+            .find(|&i| story.allows(&graph.nodes[i].when, score.0, &save))
+    });
+
+    // the line on screen fades out once the score moves or another line is
+    // waiting, but never before it has been up for its min_duration
+    let now = story.seconds;
+    if let Some(current) = &mut story.current {
+        let effects = &graph.nodes[current.node].effects;
+        if current.until.is_none() && (current.score != score.0 || next.is_some()) {
+            current.until = Some(now.max(current.since + effects.min_duration));
+        }
+        if current.until.is_some_and(|u| now >= u + effects.fade_out) {
+            story.current = None;
+        }
+    }
+
+    if let (None, Some(i)) = (&story.current, next) {
+        story.current = Some(ShownLine {
+            node: i,
+            score: score.0,
+            since: now,
+            until: None,
+            anchor: None,
+            position: None,
+        });
+        story.frontier = Some(graph.next[i].clone());
+    }
+
+    if let Some(current) = &mut story.current {
+        // it goes above the most recently spawned thingy, leaving out the ones
+        // collected this tick. When there's none yet, the next one to spawn.
+        if current.anchor.is_none() {
+            let collected: Vec<Entity> = ev.iter().map(|e| e.0).collect();
+            current.anchor = thingies
+                .iter()
+                .filter(|(ent, _, _)| !collected.contains(ent))
+                .max_by_key(|(_, _, s)| s.tick)
+                .map(|(ent, _, _)| ent);
+        }
+        // stays where it was once its thingy is gone
+        if let Some((_, transform, _)) = current.anchor.and_then(|ent| thingies.get(ent).ok()) {
+            current.position = Some(transform.translation.truncate());
+        }
+    }
+}
+
+fn draw_storyline(
+    graph: Res<StoryGraph>,
+    story: Res<StoryState>,
     mut textline: Query<(&mut Text, &mut Transform), With<TextLine>>,
 ) {
-    if let Some(stlines) = levels.get(&handles) {
-        if let Some(line) = stlines.storylines.iter().find(|&l| l.appears_at == score.0) {
-            // found a storyline for the current score
-
-            for position in thingy.iter() {
-                for (mut text, mut tr) in &mut textline {
-                    tr.translation.x = position.translation.x;
-                    tr.translation.y = position.translation.y + 15.;
-                    text.sections[0].value = line.line.to_string();
-                }
-            }
-        } else {
+    let shown = story
+        .current
+        .as_ref()
+        .and_then(|current| Some((current, current.position?)));
+    let (current, position) = match shown {
+        Some(shown) => shown,
+        None => {
             for (mut text, _tr) in &mut textline {
                 text.sections[0].value = "".to_string();
             }
+            return;
+        }
+    };
+    let now = story.seconds;
+    let node = &graph.nodes[current.node];
+    let value = line_text(&node.line, &node.effects, now - current.since, story.glitch);
+    let alpha = line_alpha(current, &node.effects, now);
+
+    for (mut text, mut tr) in &mut textline {
+        tr.translation.x = position.x;
+        tr.translation.y = position.y + TEXT_OFFSET;
+        text.sections[0].value = value.clone();
+        text.sections[0].style.color.set_a(alpha);
+    }
+}
+
+// Where the storyline text goes when it hangs above a thingy at `anchor`
+fn text_area(anchor: Vec2) -> Area {
+    Area {
+        left: anchor.x - STORY_TEXT_WIDTH / 2.,
+        bottom: anchor.y + TEXT_OFFSET,
+        right: anchor.x + STORY_TEXT_WIDTH / 2.,
+        top: anchor.y + TEXT_OFFSET + STORY_TEXT_HEIGHT,
+    }
+}
+
+// The part of the line revealed so far, with glitched characters scrambled.
+// The same `glitch` seed always scrambles the same characters.
+fn line_text(line: &str, effects: &LineEffects, elapsed: f32, glitch: u64) -> String {
+    let shown = match effects.typewriter {
+        Some(speed) => (elapsed * speed).max(0.) as usize,
+        None => usize::MAX,
+    };
+    let mut rng = StdRng::seed_from_u64(glitch);
+    line.chars()
+        .take(shown)
+        .map(|c| {
+            if c != ' ' && effects.glitch > 0. && rng.gen::<f32>() < effects.glitch {
+                GLITCH_CHARS[rng.gen_range(0..GLITCH_CHARS.len())]
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn line_alpha(current: &ShownLine, effects: &LineEffects, now: f32) -> f32 {
+    let fade_in = if effects.fade_in > 0. {
+        (now - current.since) / effects.fade_in
+    } else {
+        1.
+    };
+    let fade_out = match current.until {
+        Some(until) if effects.fade_out > 0. => 1. - (now - until) / effects.fade_out,
+        Some(until) if now >= until => 0.,
+        _ => 1.,
+    };
+    fade_in.min(fade_out).clamp(0., 1.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat_graph() -> StoryGraph {
+        let story: Story = serde_json::from_str(
+            r#"{"storylines": [
+                {"line": "first", "appears_at": 0},
+                {"line": "second", "appears_at": 100},
+                {"line": "third", "appears_at": 150},
+                {"line": "fourth", "appears_at": 200}
+            ]}"#,
+        )
+        .unwrap();
+        StoryGraph::from_story(&story)
+    }
+
+    fn lines(graph: &StoryGraph, frontier: &[usize]) -> Vec<String> {
+        frontier
+            .iter()
+            .map(|&i| graph.nodes[i].line.clone())
+            .collect()
+    }
+
+    #[test]
+    fn migrates_flat_lines_into_a_chain_in_score_order() {
+        let story: Story = serde_json::from_str(
+            r#"{"storylines": [
+                {"line": "later", "appears_at": 50},
+                {"line": "first", "appears_at": 0, "effects": {"typewriter": 20}}
+            ]}"#,
+        )
+        .unwrap();
+        let nodes = migrate_flat(&story);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].line, "first");
+        assert_eq!(nodes[0].when.min_score, Some(0));
+        assert_eq!(nodes[0].id, "at0");
+        assert_eq!(nodes[0].next, ["at50"]);
+        assert_eq!(nodes[0].effects.typewriter, Some(20.));
+        assert_eq!(nodes[1].line, "later");
+        assert_eq!(nodes[1].when.min_score, Some(50));
+        assert!(nodes[1].next.is_empty());
+
+        let graph = StoryGraph::from_story(&story);
+        assert_eq!(graph.start, [0]);
+        assert_eq!(graph.next, [vec![1], vec![]]);
+    }
+
+    #[test]
+    fn numbers_lines_sharing_a_score() {
+        let story: Story = serde_json::from_str(
+            r#"{"storylines": [
+                {"line": "one", "appears_at": 10},
+                {"line": "two", "appears_at": 10}
+            ]}"#,
+        )
+        .unwrap();
+        let ids: Vec<String> = migrate_flat(&story).into_iter().map(|n| n.id).collect();
+        assert_eq!(ids, ["at10", "at10.1"]);
+    }
+
+    fn flat_story(scores: &[i32]) -> StoryGraph {
+        let lines: Vec<String> = scores
+            .iter()
+            .map(|s| format!(r#"{{"line": "at {}", "appears_at": {}}}"#, s, s))
+            .collect();
+        let json = format!(r#"{{"storylines": [{}]}}"#, lines.join(","));
+        StoryGraph::from_story(&serde_json::from_str(&json).unwrap())
+    }
+
+    // a run that has shown the line at 100 and is waiting for the next one
+    fn waiting_after_100(graph: &StoryGraph) -> StoryState {
+        let shown = graph.index_of("at100").unwrap();
+        StoryState {
+            frontier: Some(graph.next[shown].clone()),
+            current: Some(ShownLine {
+                node: shown,
+                score: 100,
+                since: 0.,
+                until: None,
+                anchor: None,
+                position: None,
+            }),
+            ..default()
         }
     }
+
+    #[test]
+    fn reload_keeps_its_place_after_a_line_is_inserted() {
+        let old = flat_story(&[0, 100, 150, 200]);
+        let mut story = waiting_after_100(&old);
+
+        // one line goes in behind the run and one ahead of it
+        let new = flat_story(&[0, 50, 100, 150, 170, 200]);
+        story.carry_over(&old, &new, 110);
+        let frontier = story.frontier.as_ref().unwrap();
+        assert_eq!(lines(&new, frontier), ["at 150"]);
+        assert_eq!(lines(&new, &new.next[frontier[0]]), ["at 170"]);
+        let current = story.current.as_ref().unwrap();
+        assert_eq!(new.nodes[current.node].line, "at 100");
+    }
+
+    #[test]
+    fn reload_picks_up_from_the_score_when_its_line_is_removed() {
+        let old = flat_story(&[0, 100, 150, 200]);
+        let mut story = waiting_after_100(&old);
+
+        let new = flat_story(&[0, 100, 200]);
+        story.carry_over(&old, &new, 110);
+        assert_eq!(lines(&new, story.frontier.as_ref().unwrap()), ["at 200"]);
+    }
+
+    #[test]
+    fn allows_by_score_and_seconds() {
+        let when = StoryCondition {
+            min_score: Some(10),
+            max_score: Some(20),
+            min_seconds: Some(5.),
+            max_seconds: Some(30.),
+            ..default()
+        };
+        let save = SaveData::default();
+        let story = StoryState {
+            seconds: 10.,
+            ..default()
+        };
+        assert!(story.allows(&when, 10, &save));
+        assert!(story.allows(&when, 20, &save));
+        assert!(!story.allows(&when, 9, &save));
+        assert!(!story.allows(&when, 21, &save));
+
+        let early = StoryState {
+            seconds: 4.,
+            ..default()
+        };
+        assert!(!early.allows(&when, 15, &save));
+        let late = StoryState {
+            seconds: 31.,
+            ..default()
+        };
+        assert!(!late.allows(&when, 15, &save));
+    }
+
+    #[test]
+    fn allows_by_trophies() {
+        let when = StoryCondition {
+            trophies: vec!["collector".to_string(), "first steps".to_string()],
+            ..default()
+        };
+        let story = StoryState::default();
+        let mut save = SaveData::default();
+        save.unlock_trophy("collector");
+        assert!(!story.allows(&when, 0, &save));
+        save.unlock_trophy("first steps");
+        assert!(story.allows(&when, 0, &save));
+    }
+
+    #[test]
+    fn allows_by_pause_and_idle_time() {
+        let save = SaveData::default();
+        let paused = StoryCondition {
+            paused: Some(true),
+            ..default()
+        };
+        let never_paused = StoryCondition {
+            paused: Some(false),
+            ..default()
+        };
+        let mut story = StoryState::default();
+        assert!(!story.allows(&paused, 0, &save));
+        assert!(story.allows(&never_paused, 0, &save));
+        story.paused = true;
+        assert!(story.allows(&paused, 0, &save));
+        assert!(!story.allows(&never_paused, 0, &save));
+
+        let idle = StoryCondition {
+            idle_seconds: Some(3.),
+            ..default()
+        };
+        story.idle_seconds = 2.9;
+        assert!(!story.allows(&idle, 0, &save));
+        story.idle_seconds = 3.;
+        assert!(story.allows(&idle, 0, &save));
+    }
+
+    #[test]
+    fn allows_anything_without_conditions() {
+        let story = StoryState::default();
+        assert!(story.allows(&StoryCondition::default(), -5, &SaveData::default()));
+    }
+
+    #[test]
+    fn glitches_follow_the_seed() {
+        let effects = LineEffects {
+            glitch: 0.5,
+            ..default()
+        };
+        let line = "even the smallest action has a reaction";
+        let a = line_text(line, &effects, 0., 7);
+        assert_eq!(a, line_text(line, &effects, 0., 7));
+        assert_ne!(a, line);
+        assert_eq!(a.chars().count(), line.chars().count());
+        let calm = LineEffects::default();
+        assert_eq!(line_text(line, &calm, 0., 7), line);
+    }
+
+    #[test]
+    fn starts_at_the_beginning_from_zero() {
+        let graph = flat_graph();
+        assert_eq!(lines(&graph, &graph.start_from(0)), ["first"]);
+    }
+
+    #[test]
+    fn skips_lines_below_the_start_score() {
+        let graph = flat_graph();
+        assert_eq!(lines(&graph, &graph.start_from(150)), ["third"]);
+        assert_eq!(lines(&graph, &graph.start_from(120)), ["third"]);
+        assert!(graph.start_from(500).is_empty());
+    }
 }
@@ -1,64 +1,337 @@
-use crate::components::Thingy;
-use crate::{CollisionEvent, Score, ThingyAlpha, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::ball::BallSystem;
+use crate::components::{
+    Ball, SpawnSettings, SpawnStage, Spawned, Thingy, ThingyKind, ThingyKindDef, ThingyKinds,
+    Velocity,
+};
+use crate::placement::Area;
+use crate::playfield::PlayField;
+use crate::rng::GameRng;
+use crate::save::SaveData;
+use crate::storyline::{AdvanceStory, StoryState};
+use crate::{
+    on_fixed_update, AppState, CollisionEvent, FixedUpdateStage, Score, ThingyAlpha, TIMESTEP,
+};
 use bevy::prelude::*;
 use bevy_prototype_lyon::entity::ShapeBundle;
 use bevy_prototype_lyon::prelude::*;
 use rand::Rng;
+use reaction_bevy::collision::Collider;
 
 pub struct ThingyPlugin;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub struct ScoreKills;
+
 impl Plugin for ThingyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(spawn_thingy)
-            .add_system(collision_spawn);
+        // the default settings are used until the asset has loaded
+        app.insert_resource(SpawnSettings::default())
+            .insert_resource(ThingyKinds::default())
+            .insert_resource(SpawnDirector::default())
+            .add_startup_system(load_spawn_settings)
+            .add_system(apply_spawn_settings)
+            .add_system(apply_thingy_kinds)
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(reset_director))
+            .add_system_set_to_stage(
+                FixedUpdateStage,
+                on_fixed_update(AppState::InGame)
+                    .with_system(
+                        thingy_behaviours
+                            .after(BallSystem::Walls)
+                            .before(BallSystem::Broadphase),
+                    )
+                    .with_system(score_kills.label(ScoreKills).after(BallSystem::Collide))
+                    .with_system(direct_spawns.after(ScoreKills).after(AdvanceStory)),
+            );
+    }
+}
+
+const THINGY_SIDE: f32 = 30.0;
+
+// Keeps the field stocked with thingies. Time is counted in fixed ticks so
+// replays spawn on the same ticks.
+#[derive(Default)]
+struct SpawnDirector {
+    tick: u32,
+    last_spawn: Option<u32>,
+}
+
+fn load_spawn_settings(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handle: Handle<SpawnSettings> = asset_server.load("default.spawns.json");
+    commands.insert_resource(handle);
+    let handle: Handle<ThingyKinds> = asset_server.load("default.thingies.json");
+    commands.insert_resource(handle);
+}
+
+fn apply_spawn_settings(
+    mut ev_asset: EventReader<AssetEvent<SpawnSettings>>,
+    assets: Res<Assets<SpawnSettings>>,
+    handle: Res<Handle<SpawnSettings>>,
+    mut settings: ResMut<SpawnSettings>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Created { handle: h } | AssetEvent::Modified { handle: h } = ev {
+            if *h != *handle {
+                continue;
+            }
+            if let Some(s) = assets.get(h) {
+                *settings = s.clone();
+            }
+        }
+    }
+}
+
+fn apply_thingy_kinds(
+    mut ev_asset: EventReader<AssetEvent<ThingyKinds>>,
+    assets: Res<Assets<ThingyKinds>>,
+    handle: Res<Handle<ThingyKinds>>,
+    mut kinds: ResMut<ThingyKinds>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Created { handle: h } | AssetEvent::Modified { handle: h } = ev {
+            if *h != *handle {
+                continue;
+            }
+            if let Some(k) = assets.get(h) {
+                *kinds = k.clone();
+            }
+        }
+    }
+}
+
+fn reset_director(mut director: ResMut<SpawnDirector>) {
+    *director = SpawnDirector::default();
+}
+
+fn seconds_to_ticks(seconds: f32) -> u32 {
+    (seconds as f64 / TIMESTEP).round() as u32
+}
+
+fn current_stage(settings: &SpawnSettings, score: i32, tick: u32) -> SpawnStage {
+    settings
+        .stages
+        .iter()
+        .rfind(|s| score >= s.from_score && tick >= seconds_to_ticks(s.after_seconds))
+        .cloned()
+        .unwrap_or_else(|| SpawnSettings::default().stages[0].clone())
+}
+
+// Weighted random pick from the kind table
+fn pick_kind<'a>(kinds: &'a ThingyKinds, rng: &mut GameRng) -> Option<&'a ThingyKindDef> {
+    let total: u32 = kinds.kinds.iter().map(|k| k.weight).sum();
+    if total == 0 {
+        return None;
     }
+    let mut roll = rng.rng.gen_range(0..total);
+    kinds.kinds.iter().find(|k| {
+        if roll < k.weight {
+            true
+        } else {
+            roll -= k.weight;
+            false
+        }
+    })
 }
 
-pub fn spawn_thingy(mut commands: Commands) {
-    let t = create_thingy(1.);
-    commands.spawn_bundle(t).insert(Thingy);
+fn kind_color(def: Option<&ThingyKindDef>, alpha: f32) -> Color {
+    let mut color = def
+        .and_then(|d| d.color.as_deref())
+        .map_or(Color::BLACK, |hex| {
+            Color::hex(hex.trim_start_matches('#')).unwrap_or_else(|_| {
+                eprintln!("invalid thingy color: {}", hex);
+                Color::BLACK
+            })
+        });
+    color.set_a(alpha);
+    color
 }
 
-fn create_thingy(alpha: f32) -> ShapeBundle {
-    let y_upper = (WINDOW_HEIGHT / 2.) - 110.;
-    let y_lower = -(WINDOW_HEIGHT / 2.) + 60.;
+// A spot for a new thingy that follows the placement rules and stays out
+// from under `text`, the storyline text on screen
+fn place_thingy(
+    settings: &SpawnSettings,
+    rng: &mut GameRng,
+    field: &PlayField,
+    ball: Option<Vec2>,
+    others: &[Vec2],
+    text: Option<Area>,
+) -> Vec2 {
+    let (lower, upper) = field.spawn_area();
+    let covered: Vec<Area> = text
+        .map(|a| a.grown(THINGY_SIDE / 2.))
+        .into_iter()
+        .collect();
+    settings
+        .placement
+        .place(rng, lower, upper, ball, others, &covered)
+}
 
-    let x_upper = -(WINDOW_WIDTH / 2.0) + 80.;
-    let x_lower = (WINDOW_WIDTH / 2.0) - 80.;
+fn create_thingy(color: Color, pos: Vec2) -> ShapeBundle {
     let t = shapes::Rectangle {
         origin: RectangleOrigin::Center,
-        extents: Vec2 { x: 30.0, y: 30.0 },
+        extents: Vec2::splat(THINGY_SIDE),
     };
 
-    let mut rng = rand::thread_rng();
-    let color: Color = Color::rgba(0.0, 0.0, 0.0, alpha);
-
     GeometryBuilder::build_as(
         &t,
         DrawMode::Fill(FillMode::color(color)),
-        Transform {
-            translation: Vec3 {
-                x: rng.gen_range(x_upper..x_lower) as f32,
-                y: rng.gen_range(y_lower..y_upper) as f32,
-                z: 10.,
-            },
-            ..default()
-        },
+        Transform::from_translation(pos.extend(10.)),
     )
 }
 
-fn collision_spawn(
-    mut commands: Commands,
+// Kills are counted for the save file here too, while the collected thingy is
+// still around to tell a decoy from a kill
+fn score_kills(
     mut ev: EventReader<CollisionEvent>,
     mut score: ResMut<Score>,
-    mut alpha: ResMut<ThingyAlpha>,
+    mut save: ResMut<SaveData>,
+    kinds: Query<&ThingyKind>,
 ) {
-    if ev.iter().next().is_some() {
-        if score.0 > 100 {
-            alpha.0 -= 0.01;
+    for collision in ev.iter() {
+        // the collected thingy is despawned at the end of the stage, so it's still here
+        if let Ok(ThingyKind::Decoy { penalty, .. }) = kinds.get(collision.0) {
+            score.0 = (score.0 - penalty).max(0);
+            continue;
         }
-        let t = create_thingy(alpha.0);
-        commands.spawn_bundle(t).insert(Thingy);
         score.0 += 1;
+        save.total_kills += 1;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn direct_spawns(
+    mut commands: Commands,
+    mut director: ResMut<SpawnDirector>,
+    mut ev: EventReader<CollisionEvent>,
+    settings: Res<SpawnSettings>,
+    kinds: Res<ThingyKinds>,
+    score: Res<Score>,
+    alpha: Res<ThingyAlpha>,
+    mut rng: ResMut<GameRng>,
+    field: Res<PlayField>,
+    story: Res<StoryState>,
+    ball: Query<&Transform, With<Ball>>,
+    thingies: Query<(Entity, &Transform, &Spawned), With<Thingy>>,
+) {
+    let tick = director.tick;
+    director.tick += 1;
+    let stage = current_stage(&settings, score.0, tick);
+
+    // collected thingies are only despawned at the end of the stage, so
+    // leave them out of the count
+    let collected: Vec<Entity> = ev.iter().map(|e| e.0).collect();
+    let mut alive = Vec::new();
+    for (ent, transform, spawned) in thingies.iter() {
+        if collected.contains(&ent) {
+            continue;
+        }
+        if spawned.expires.is_some_and(|t| tick >= t) {
+            commands.entity(ent).despawn();
+            continue;
+        }
+        alive.push(transform.translation.truncate());
+    }
+
+    let ball = ball.get_single().ok().map(|b| b.translation.truncate());
+    let interval = seconds_to_ticks(stage.interval);
+    while alive.len() < stage.alive {
+        if let Some(last) = director.last_spawn {
+            if tick - last < interval {
+                break;
+            }
+        }
+        let def = pick_kind(&kinds, &mut rng);
+        let kind = def.map_or(ThingyKind::Static, |d| d.kind);
+        let pos = place_thingy(&settings, &mut rng, &field, ball, &alive, story.text_area());
+        let t = create_thingy(kind_color(def, alpha.0), pos);
+
+        let mut lifetime = stage.lifetime;
+        if let ThingyKind::Decoy { lifetime: l, .. } = kind {
+            lifetime = Some(lifetime.map_or(l, |s| s.min(l)));
+        }
+        let mut thingy = commands.spawn_bundle(t);
+        thingy
+            .insert(Thingy)
+            .insert(kind)
+            .insert(Collider::square(THINGY_SIDE))
+            .insert(Spawned {
+                tick,
+                expires: lifetime.map(|l| tick + seconds_to_ticks(l)),
+            });
+        if let ThingyKind::Drift { speed } = kind {
+            let angle = rng.rng.gen_range(0.0..std::f32::consts::TAU);
+            thingy.insert(Velocity(Vec2::new(angle.cos(), angle.sin()) * speed));
+        }
+        director.last_spawn = Some(tick);
+        alive.push(pos);
+        if interval > 0 {
+            break;
+        }
+    }
+}
+
+pub fn nearest_thingy<'a>(
+    pos: Vec2,
+    thingies: impl Iterator<Item = &'a Transform>,
+) -> Option<&'a Transform> {
+    thingies.min_by(|a, b| {
+        let da = a.translation.truncate().distance_squared(pos);
+        let db = b.translation.truncate().distance_squared(pos);
+        da.total_cmp(&db)
+    })
+}
+
+#[allow(clippy::type_complexity)]
+fn thingy_behaviours(
+    mut rng: ResMut<GameRng>,
+    field: Res<PlayField>,
+    settings: Res<SpawnSettings>,
+    director: Res<SpawnDirector>,
+    ball: Query<&Transform, With<Ball>>,
+    mut thingies: Query<
+        (&mut Transform, &ThingyKind, &Spawned, Option<&mut Velocity>),
+        (With<Thingy>, Without<Ball>),
+    >,
+) {
+    let dt = TIMESTEP as f32;
+    let (lower, upper) = field.spawn_area();
+    let ball = ball.get_single().ok().map(|b| b.translation.truncate());
+
+    for (mut transform, kind, spawned, velocity) in thingies.iter_mut() {
+        let pos = transform.translation.truncate();
+        let new_pos = match *kind {
+            ThingyKind::Drift { .. } => match velocity {
+                Some(mut velocity) => {
+                    let next = pos + velocity.0 * dt;
+                    if next.x < lower.x || next.x > upper.x {
+                        velocity.0.x = -velocity.0.x;
+                    }
+                    if next.y < lower.y || next.y > upper.y {
+                        velocity.0.y = -velocity.0.y;
+                    }
+                    next.clamp(lower, upper)
+                }
+                None => pos,
+            },
+            ThingyKind::Flee { speed, range } => match ball {
+                Some(ball) if ball.distance(pos) < range => {
+                    let away = (pos - ball).normalize_or_zero();
+                    (pos + away * speed * dt).clamp(lower, upper)
+                }
+                _ => pos,
+            },
+            ThingyKind::Teleport { every } => {
+                let every = seconds_to_ticks(every).max(1);
+                let age = director.tick - spawned.tick;
+                if age > 0 && age.is_multiple_of(every) {
+                    place_thingy(&settings, &mut rng, &field, ball, &[], None)
+                } else {
+                    pos
+                }
+            }
+            ThingyKind::Static | ThingyKind::Decoy { .. } => pos,
+        };
+        transform.translation.x = new_pos.x;
+        transform.translation.y = new_pos.y;
     }
 }
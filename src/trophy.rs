@@ -5,10 +5,15 @@ pub struct TrophyPlugin;
 
 use crate::components::Trophy;
 use crate::components::TrophyText;
-use crate::{Score, WINDOW_HEIGHT};
+use crate::components::{TrophyDef, TrophyDefs};
+use crate::playfield::PlayField;
+use crate::save::SaveData;
+use crate::{AppState, Score};
 
 #[derive(Component, Debug)]
 struct LocalTrophy {
+    name: String,
+    description: String,
     score: i32,
     square_color: Color,
     circle_color: Color,
@@ -20,40 +25,121 @@ struct TrophySquare;
 
 impl Plugin for TrophyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(spawn_trophy)
+        app.add_startup_system(load_trophies)
+            .add_system(spawn_trophies)
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(reset_trophies))
             .add_system(update_scoretext)
             .add_system(update_trophy_colors);
     }
 }
 
-fn spawn_trophy(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let trophies: Vec<LocalTrophy> = vec![
-        LocalTrophy {
-            score: 10,
-            square_color: Color::RED,
-            circle_color: Color::BLUE,
-            achieved: false,
-        },
-        LocalTrophy {
-            score: 50,
-            square_color: Color::YELLOW,
-            circle_color: Color::PINK,
-            achieved: false,
-        },
+fn load_trophies(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handle: Handle<TrophyDefs> = asset_server.load("default.trophies.json");
+    commands.insert_resource(handle);
+}
+
+fn parse_color(hex: &str) -> Color {
+    Color::hex(hex.trim_start_matches('#')).unwrap_or_else(|_| {
+        eprintln!("invalid trophy color: {}", hex);
+        Color::GRAY
+    })
+}
+
+impl From<&TrophyDef> for LocalTrophy {
+    fn from(def: &TrophyDef) -> Self {
         LocalTrophy {
-            score: 110,
-            square_color: Color::BLACK,
-            circle_color: Color::BLACK,
+            name: def.name.clone(),
+            description: def.description.clone(),
+            score: def.score,
+            square_color: parse_color(&def.square_color),
+            circle_color: parse_color(&def.circle_color),
             achieved: false,
-        },
-    ];
+        }
+    }
+}
+
+// the trophy row is spawned once the definitions have finished loading,
+// and again whenever the file is edited
+#[allow(clippy::too_many_arguments)]
+fn spawn_trophies(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut ev_asset: EventReader<AssetEvent<TrophyDefs>>,
+    assets: Res<Assets<TrophyDefs>>,
+    handle: Res<Handle<TrophyDefs>>,
+    save: Res<SaveData>,
+    field: Res<PlayField>,
+    row: Query<Entity, With<Trophy>>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Created { handle: h } | AssetEvent::Modified { handle: h } = ev {
+            if *h != *handle {
+                continue;
+            }
+            if let Some(defs) = assets.get(h) {
+                // an edited file replaces the whole row
+                for ent in row.iter() {
+                    commands.entity(ent).despawn_recursive();
+                }
+                spawn_trophy_row(&mut commands, &asset_server, defs, &save, &field);
+            }
+        }
+    }
+}
 
-    let mut x = -100.;
-    let y = WINDOW_HEIGHT / 2. - 20.;
-    let stroke_width = 3.;
+// a new run starts with only the trophies unlocked in the save file
+fn reset_trophies(
+    save: Res<SaveData>,
+    mut trophies: Query<(&mut DrawMode, &mut LocalTrophy, &Children), With<Trophy>>,
+    mut q_child: Query<&mut DrawMode, (With<TrophySquare>, Without<Trophy>)>,
+) {
+    for (mut circle_draw, mut lt, children) in trophies.iter_mut() {
+        lt.achieved = save.has_trophy(&lt.name);
+        let (circle_mode, square_mode) = if lt.achieved {
+            achieved_draw_modes(&lt)
+        } else {
+            unachieved_draw_modes()
+        };
+        *circle_draw = circle_mode;
+        for &child in children.iter() {
+            if let Ok(mut dm) = q_child.get_mut(child) {
+                *dm = square_mode;
+            }
+        }
+    }
+}
+
+fn spawn_trophy_row(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    defs: &TrophyDefs,
+    save: &SaveData,
+    field: &PlayField,
+) {
+    let trophies: Vec<LocalTrophy> = defs
+        .trophies
+        .iter()
+        .map(|def| LocalTrophy {
+            // unlocked in an earlier session
+            achieved: save.has_trophy(&def.name),
+            ..LocalTrophy::from(def)
+        })
+        .collect();
+
+    // in the band above the play field
+    let y = field.max.y + 20.;
     let x_offset = 50.;
+    // keep the row centered on the same spot regardless of how many trophies there are
+    let mut x = -50. - (trophies.len().saturating_sub(1) as f32) * x_offset / 2.;
 
     for lt in trophies {
+        let score = lt.score;
+        let (circle_mode, square_mode) = if lt.achieved {
+            achieved_draw_modes(&lt)
+        } else {
+            unachieved_draw_modes()
+        };
+
         let ball = shapes::Circle {
             radius: 15.0,
             center: Vec2 { x: 0.0, y: 0.0 },
@@ -62,19 +148,14 @@ fn spawn_trophy(mut commands: Commands, asset_server: Res<AssetServer>) {
         let b = commands
             .spawn_bundle(GeometryBuilder::build_as(
                 &ball,
-                DrawMode::Stroke(StrokeMode::new(Color::GRAY, stroke_width)),
+                circle_mode,
                 Transform {
                     translation: Vec3 { x, y, z: 9. },
                     ..default()
                 },
             ))
             .insert(Trophy)
-            .insert(LocalTrophy {
-                score: lt.score,
-                square_color: lt.square_color,
-                circle_color: lt.circle_color,
-                achieved: lt.achieved,
-            })
+            .insert(lt)
             .id();
 
         let square = shapes::Rectangle {
@@ -85,7 +166,7 @@ fn spawn_trophy(mut commands: Commands, asset_server: Res<AssetServer>) {
         let s = commands
             .spawn_bundle(GeometryBuilder::build_as(
                 &square,
-                DrawMode::Fill(FillMode::color(Color::GRAY)),
+                square_mode,
                 Transform {
                     translation: Vec3 {
                         x: 0.,
@@ -125,7 +206,7 @@ fn spawn_trophy(mut commands: Commands, asset_server: Res<AssetServer>) {
                 },
                 ..default()
             })
-            .insert(TrophyText { score: lt.score })
+            .insert(TrophyText { score })
             .id();
 
         commands.entity(b).push_children(&[t]);
@@ -147,8 +228,25 @@ fn update_scoretext(
     }
 }
 
+// circle and square draw modes of a trophy that hasn't been achieved yet
+fn unachieved_draw_modes() -> (DrawMode, DrawMode) {
+    (
+        DrawMode::Stroke(StrokeMode::new(Color::GRAY, 3.)),
+        DrawMode::Fill(FillMode::color(Color::GRAY)),
+    )
+}
+
+// circle and square draw modes of an achieved trophy
+fn achieved_draw_modes(lt: &LocalTrophy) -> (DrawMode, DrawMode) {
+    (
+        DrawMode::Stroke(StrokeMode::new(lt.circle_color, 3.)),
+        DrawMode::Stroke(StrokeMode::new(lt.square_color, 3.)),
+    )
+}
+
 fn update_trophy_colors(
     score: Res<Score>,
+    mut save: ResMut<SaveData>,
     mut trophies: Query<(&Trophy, &mut DrawMode, &mut LocalTrophy, &Children)>,
     mut q_child: Query<(&TrophySquare, &mut DrawMode), Without<Trophy>>,
 ) {
@@ -158,11 +256,14 @@ fn update_trophy_colors(
         }
         if score.0 >= lt.score {
             lt.achieved = true;
-            *circle_draw = DrawMode::Stroke(StrokeMode::new(lt.circle_color, 3.));
+            println!("trophy unlocked: {} - {}", lt.name, lt.description);
+            save.unlock_trophy(&lt.name);
+            let (circle_mode, square_mode) = achieved_draw_modes(&lt);
+            *circle_draw = circle_mode;
             for &child in children.iter() {
                 match q_child.get_mut(child) {
                     Ok((_c, mut dm)) => {
-                        *dm = DrawMode::Stroke(StrokeMode::new(lt.square_color, 3.));
+                        *dm = square_mode;
                     }
                     Err(_) => continue,
                 }
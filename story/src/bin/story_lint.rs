@@ -0,0 +1,379 @@
+// Checks storyline files for mistakes that would otherwise fail silently in
+// game. `cargo run --bin story-lint [--game-over <score>] [files...]`, by
+// default every assets/storylines*.json. Exits with 1 when there are errors.
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use reaction_story::{LineEffects, Story, STORY_FONT_SIZE, STORY_TEXT_HEIGHT, STORY_TEXT_WIDTH};
+
+const FONT_FILE: &str = "ProggyClean.ttf";
+const DEFAULT_GAME_OVER: i32 = 201;
+
+// the game's assets folder, found from this crate so the tool runs from any directory
+fn assets_dir() -> PathBuf {
+    let story_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    story_dir.parent().unwrap_or(story_dir).join("assets")
+}
+
+fn load_font() -> Option<FontVec> {
+    let bytes = fs::read(assets_dir().join(FONT_FILE)).ok()?;
+    FontVec::try_from_vec(bytes).ok()
+}
+
+#[derive(Default)]
+struct Report {
+    errors: u32,
+    warnings: u32,
+}
+
+impl Report {
+    fn error(&mut self, file: &Path, msg: String) {
+        self.errors += 1;
+        println!("error: {}: {}", file.display(), msg);
+    }
+
+    fn warning(&mut self, file: &Path, msg: String) {
+        self.warnings += 1;
+        println!("warning: {}: {}", file.display(), msg);
+    }
+}
+
+fn main() {
+    let mut game_over = DEFAULT_GAME_OVER;
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--game-over" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(score) => game_over = score,
+                None => {
+                    eprintln!("--game-over needs a score");
+                    process::exit(2);
+                }
+            },
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if files.is_empty() {
+        files = default_files();
+    }
+
+    let font = match load_font() {
+        Some(font) => font,
+        None => {
+            eprintln!("could not load {}", assets_dir().join(FONT_FILE).display());
+            process::exit(2);
+        }
+    };
+
+    let mut report = Report::default();
+    for file in files.iter() {
+        lint_file(file, game_over, &font, &mut report);
+    }
+    println!(
+        "{} files, {} errors, {} warnings",
+        files.len(),
+        report.errors,
+        report.warnings
+    );
+    if report.errors > 0 {
+        process::exit(1);
+    }
+}
+
+fn default_files() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(assets_dir())
+        .map(|dir| {
+            dir.filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| {
+                    p.file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.starts_with("storylines") && n.ends_with(".json"))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+fn lint_file(file: &Path, game_over: i32, font: &FontVec, report: &mut Report) {
+    let story: Story = match fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+    {
+        Ok(story) => story,
+        Err(e) => {
+            report.error(file, e);
+            return;
+        }
+    };
+
+    if story.storylines.is_empty() && story.nodes.is_empty() {
+        report.warning(file, "no storylines".to_string());
+    }
+    if !story.storylines.is_empty() && !story.nodes.is_empty() {
+        report.warning(
+            file,
+            "has both `storylines` and `nodes`, the flat list is ignored".to_string(),
+        );
+    }
+
+    // the flat format
+    let mut seen: HashMap<i32, &str> = HashMap::new();
+    for l in story.storylines.iter() {
+        let name = format!("line at {}", l.appears_at);
+        if let Some(first) = seen.insert(l.appears_at, &l.line) {
+            report.warning(
+                file,
+                format!(
+                    "{}: \"{}\" replaces \"{}\" straight away",
+                    name, l.line, first
+                ),
+            );
+        }
+        if l.appears_at >= game_over {
+            report.error(
+                file,
+                format!("{}: the game is over at {}, never shown", name, game_over),
+            );
+        }
+        lint_line(file, &name, &l.line, &l.effects, font, report);
+    }
+
+    // the node graph
+    let mut ids = HashSet::new();
+    for node in story.nodes.iter() {
+        if !ids.insert(node.id.as_str()) {
+            report.error(file, format!("node {}: duplicate id", node.id));
+        }
+    }
+    for id in story.start.iter() {
+        if !ids.contains(id.as_str()) {
+            report.error(file, format!("start: unknown node {}", id));
+        }
+    }
+    for node in story.nodes.iter() {
+        let name = format!("node {}", node.id);
+        for next in node.next.iter() {
+            if !ids.contains(next.as_str()) {
+                report.error(file, format!("{}: unknown next node {}", name, next));
+            }
+        }
+        let when = &node.when;
+        if when.min_score.is_some_and(|s| s >= game_over) {
+            report.error(
+                file,
+                format!("{}: the game is over at {}, never shown", name, game_over),
+            );
+        }
+        if let (Some(min), Some(max)) = (when.min_score, when.max_score) {
+            if min > max {
+                report.error(file, format!("{}: min_score is above max_score", name));
+            }
+        }
+        if let (Some(min), Some(max)) = (when.min_seconds, when.max_seconds) {
+            if min > max {
+                report.error(file, format!("{}: min_seconds is above max_seconds", name));
+            }
+        }
+        lint_line(file, &name, &node.line, &node.effects, font, report);
+    }
+    for id in unreachable(&story) {
+        report.warning(
+            file,
+            format!("node {}: can't be reached from the start", id),
+        );
+    }
+}
+
+// Nodes no path from the start leads to
+fn unreachable(story: &Story) -> Vec<&str> {
+    let nodes: HashMap<&str, &Vec<String>> = story
+        .nodes
+        .iter()
+        .map(|n| (n.id.as_str(), &n.next))
+        .collect();
+    let mut stack: Vec<&str> = if story.start.is_empty() {
+        story
+            .nodes
+            .first()
+            .map(|n| n.id.as_str())
+            .into_iter()
+            .collect()
+    } else {
+        story.start.iter().map(|s| s.as_str()).collect()
+    };
+    let mut reached = HashSet::new();
+    while let Some(id) = stack.pop() {
+        if reached.insert(id) {
+            if let Some(next) = nodes.get(id) {
+                stack.extend(next.iter().map(|s| s.as_str()));
+            }
+        }
+    }
+    story
+        .nodes
+        .iter()
+        .map(|n| n.id.as_str())
+        .filter(|id| !reached.contains(id))
+        .collect()
+}
+
+fn lint_line(
+    file: &Path,
+    name: &str,
+    line: &str,
+    effects: &LineEffects,
+    font: &FontVec,
+    report: &mut Report,
+) {
+    if line.trim().is_empty() {
+        report.warning(file, format!("{}: empty line", name));
+    }
+    if !(0. ..=1.).contains(&effects.glitch) {
+        report.warning(file, format!("{}: glitch should be between 0 and 1", name));
+    }
+    if effects.typewriter.is_some_and(|speed| speed <= 0.) {
+        report.error(file, format!("{}: typewriter speed must be positive", name));
+    }
+
+    let layout = measure(line, font);
+    if layout.widest > STORY_TEXT_WIDTH {
+        report.error(
+            file,
+            format!(
+                "{}: \"{}\" is {:.0}px wide and can't wrap into {:.0}px",
+                name, layout.widest_word, layout.widest, STORY_TEXT_WIDTH
+            ),
+        );
+    }
+    if layout.height > STORY_TEXT_HEIGHT {
+        report.error(
+            file,
+            format!(
+                "{}: wraps to {} rows, {:.0}px tall, over the {:.0}px bounds",
+                name, layout.rows, layout.height, STORY_TEXT_HEIGHT
+            ),
+        );
+    }
+}
+
+struct Layout {
+    rows: u32,
+    height: f32,
+    widest: f32,
+    widest_word: String,
+}
+
+// Wraps at spaces like the in-game text does and measures the result
+fn measure(line: &str, font: &FontVec) -> Layout {
+    let font = font.as_scaled(PxScale::from(STORY_FONT_SIZE));
+    let width = |s: &str| -> f32 {
+        let mut last = None;
+        let mut w = 0.;
+        for c in s.chars() {
+            let id = font.glyph_id(c);
+            if let Some(prev) = last {
+                w += font.kern(prev, id);
+            }
+            w += font.h_advance(id);
+            last = Some(id);
+        }
+        w
+    };
+
+    let space = width(" ");
+    let mut rows = 1;
+    let mut row_width = 0.;
+    let mut widest = 0.;
+    let mut widest_word = String::new();
+    for word in line.split(' ') {
+        let w = width(word);
+        if w > widest {
+            widest = w;
+            widest_word = word.to_string();
+        }
+        if row_width > 0. && row_width + space + w > STORY_TEXT_WIDTH {
+            rows += 1;
+            row_width = w;
+        } else if row_width > 0. {
+            row_width += space + w;
+        } else {
+            row_width = w;
+        }
+    }
+
+    let row_height = font.height() + font.line_gap();
+    Layout {
+        rows,
+        height: rows as f32 * row_height,
+        widest,
+        widest_word,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn story(json: &str) -> Story {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn finds_unreachable_nodes() {
+        let story = story(
+            r#"{"start": ["a"], "nodes": [
+                {"id": "a", "line": "a", "next": ["b"]},
+                {"id": "b", "line": "b", "next": ["a"]},
+                {"id": "c", "line": "c", "next": ["b"]}
+            ]}"#,
+        );
+        assert_eq!(unreachable(&story), ["c"]);
+    }
+
+    #[test]
+    fn starts_from_the_first_node_without_a_start() {
+        let story = story(
+            r#"{"nodes": [
+                {"id": "a", "line": "a", "next": ["c"]},
+                {"id": "b", "line": "b"},
+                {"id": "c", "line": "c", "next": ["missing"]}
+            ]}"#,
+        );
+        assert_eq!(unreachable(&story), ["b"]);
+    }
+
+    #[test]
+    fn measures_a_short_line_on_one_row() {
+        let font = load_font().unwrap();
+        let layout = measure("hello", &font);
+        assert_eq!(layout.rows, 1);
+        assert_eq!(layout.widest_word, "hello");
+        assert!(layout.widest > 0. && layout.widest < STORY_TEXT_WIDTH);
+    }
+
+    #[test]
+    fn wraps_a_long_line_at_spaces() {
+        let font = load_font().unwrap();
+        let one = measure("word", &font);
+        let layout = measure(&["word"; 40].join(" "), &font);
+        assert!(layout.rows > 1);
+        assert!(layout.widest <= STORY_TEXT_WIDTH);
+        assert_eq!(layout.height, layout.rows as f32 * one.height);
+    }
+
+    #[test]
+    fn reports_a_word_too_wide_to_wrap() {
+        let font = load_font().unwrap();
+        let word = "x".repeat(60);
+        let layout = measure(&format!("a {} b", word), &font);
+        assert_eq!(layout.widest_word, word);
+        assert!(layout.widest > STORY_TEXT_WIDTH);
+    }
+}
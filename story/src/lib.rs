@@ -0,0 +1,73 @@
+// The storyline file format, shared by the game and story-lint
+use serde::Deserialize;
+
+// storyline text is ProggyClean at this size, wrapped to this box
+pub const STORY_FONT_SIZE: f32 = 15.;
+pub const STORY_TEXT_WIDTH: f32 = 150.;
+pub const STORY_TEXT_HEIGHT: f32 = 200.;
+
+#[derive(Debug, Deserialize)]
+pub struct Story {
+    // the old flat format, read as a chain of nodes
+    #[serde(default)]
+    pub storylines: Vec<StoryLine>,
+    #[serde(default)]
+    pub nodes: Vec<StoryNode>,
+    // ids of the nodes the story can begin with, the first node when empty
+    #[serde(default)]
+    pub start: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StoryLine {
+    pub line: String,
+    pub appears_at: i32,
+    #[serde(default)]
+    pub effects: LineEffects,
+}
+
+// How a line shows up and goes away, times in seconds
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LineEffects {
+    // characters revealed per second, the whole line at once when missing
+    pub typewriter: Option<f32>,
+    // the line stays up at least this long, even when the score moves on
+    pub min_duration: f32,
+    pub fade_in: f32,
+    pub fade_out: f32,
+    // share of the characters scrambled at any moment, 0 to 1
+    pub glitch: f32,
+}
+
+// A line that is shown once its condition holds, after which the story
+// carries on from the first of `next` whose condition holds
+#[derive(Debug, Clone, Deserialize)]
+pub struct StoryNode {
+    pub id: String,
+    pub line: String,
+    #[serde(default)]
+    pub when: StoryCondition,
+    // no next nodes ends this branch of the story
+    #[serde(default)]
+    pub next: Vec<String>,
+    #[serde(default)]
+    pub effects: LineEffects,
+}
+
+// Every field that is set has to hold
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StoryCondition {
+    pub min_score: Option<i32>,
+    pub max_score: Option<i32>,
+    // seconds played this run, time spent paused doesn't count
+    pub min_seconds: Option<f32>,
+    pub max_seconds: Option<f32>,
+    // names of trophies the player has unlocked
+    pub trophies: Vec<String>,
+    // whether the player has paused during this run
+    pub paused: Option<bool>,
+    // the player hasn't moved for at least this many seconds
+    pub idle_seconds: Option<f32>,
+}
@@ -0,0 +1,25 @@
+// Plays a whole run with the autopilot and checks it reaches the game over
+use std::process::Command;
+
+#[test]
+fn headless_run_reaches_game_over() {
+    let output = Command::new(env!("CARGO_BIN_EXE_reaction-bevy"))
+        .args(["--headless", "--seed", "42"])
+        // assets are looked up from the manifest directory
+        .env("CARGO_MANIFEST_DIR", env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("could not run the game");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "headless run failed: {}\n{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("headless run finished"),
+        "no game over in the output: {}",
+        stdout
+    );
+}